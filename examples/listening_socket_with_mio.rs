@@ -92,7 +92,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
-    let outcome = future::block_on(test);
+    let outcome = future::block_on(test)?;
     println!("outcome: {}", outcome);
 
     Ok(())
@@ -4,6 +4,7 @@ use async_native_tls::TlsStream;
 use futures_lite::future;
 use http::Uri;
 use hyper::{Body, Client, Request, Response};
+use rust_concurrency::hyper_client::BodyStream;
 use rust_concurrency::runtime::{spawn_task_function, FutureType, Runtime};
 use smol::{io, prelude::*, Async};
 use std::net::Shutdown;
@@ -173,12 +174,15 @@ fn main() {
             .body(Body::empty())
             .unwrap();
         let response = fetch(req).await.unwrap();
-        let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
 
-        let html = String::from_utf8(body_bytes.to_vec()).unwrap();
-        println!("{}", html);
+        // Print the page as it arrives instead of buffering it whole, and
+        // stop if it grows unreasonably large.
+        let mut body = BodyStream::new(response.into_body()).max_size(10 * 1024 * 1024);
+        while let Some(chunk) = body.next().await {
+            std::io::Write::write_all(&mut std::io::stdout(), &chunk.unwrap()).unwrap();
+        }
     };
 
     let test = spawn_task!(future);
-    future::block_on(test);
+    future::block_on(test).unwrap();
 }
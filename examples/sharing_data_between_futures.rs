@@ -1,11 +1,13 @@
 use std::{
     future::Future,
     pin::Pin,
-    sync::{Arc, Mutex},
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
 
+use rust_concurrency::sync::Mutex;
+
 #[derive(Debug)]
 enum CounterType {
     Increment,
@@ -27,7 +29,7 @@ impl SharedData {
 
 struct CounterFuture {
     counter_type: CounterType,
-    data_reference: Arc<Mutex<SharedData>>,
+    data_reference: Arc<std::sync::Mutex<SharedData>>,
     count: u32,
 }
 
@@ -67,7 +69,7 @@ impl Future for CounterFuture {
 
 async fn count(
     count: u32,
-    data: Arc<tokio::sync::Mutex<SharedData>>,
+    data: Arc<Mutex<SharedData>>,
     counter_type: CounterType,
 ) -> u32 {
     for _ in 0..count {
@@ -91,7 +93,7 @@ async fn count(
 #[tokio::main]
 async fn main() {
     // // low level shared data between futures
-    // let shared_data = Arc::new(Mutex::new(SharedData { counter: 0 }));
+    // let shared_data = Arc::new(std::sync::Mutex::new(SharedData { counter: 0 }));
     // let shared_data_clone = shared_data.clone();
     // let counter_one = CounterFuture {
     //     counter_type: CounterType::Increment,
@@ -108,7 +110,7 @@ async fn main() {
     // tokio::join!(handle_one, handle_two);
 
     // high level shared data between futures
-    let shared_data = Arc::new(tokio::sync::Mutex::new(SharedData { counter: 0 }));
+    let shared_data = Arc::new(Mutex::new(SharedData { counter: 0 }));
     let shared_data_clone = shared_data.clone();
     let handle_one =
         tokio::task::spawn(
@@ -0,0 +1,65 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{Expr, ItemFn, Lit, Meta, Token};
+
+/// Runs an `async fn` test on `rust_concurrency::test`'s deterministic
+/// runtime.
+///
+/// Accepts `seed = <u64>` and `max_steps = <u64>`, which are forwarded to
+/// `rust_concurrency::test::Builder`.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    match expand(args.into(), item.into()) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(
+    args: proc_macro2::TokenStream,
+    item: proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut input: ItemFn = syn::parse2(item)?;
+    if input.sig.asyncness.take().is_none() {
+        return Err(syn::Error::new_spanned(
+            input.sig.fn_token,
+            "#[rust_concurrency::test] requires an async fn",
+        ));
+    }
+
+    let mut builder = quote!(::rust_concurrency::test::Builder::new());
+    let args = Punctuated::<Meta, Token![,]>::parse_terminated.parse2(args)?;
+    for arg in args {
+        let Meta::NameValue(arg) = arg else {
+            return Err(syn::Error::new_spanned(arg, "expected `name = value`"));
+        };
+        let value = match &arg.value {
+            Expr::Lit(lit) => match &lit.lit {
+                Lit::Int(int) => int.base10_parse::<u64>()?,
+                other => return Err(syn::Error::new_spanned(other, "expected an integer")),
+            },
+            other => return Err(syn::Error::new_spanned(other, "expected an integer")),
+        };
+        if arg.path.is_ident("seed") {
+            builder = quote!(#builder.seed(#value));
+        } else if arg.path.is_ident("max_steps") {
+            builder = quote!(#builder.max_steps(#value));
+        } else {
+            return Err(syn::Error::new_spanned(arg.path, "unknown argument"));
+        }
+    }
+
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let sig = &input.sig;
+    let body = &input.block;
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis #sig {
+            #builder.block_on(async #body)
+        }
+    })
+}
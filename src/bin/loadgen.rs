@@ -0,0 +1,704 @@
+//! A load generator for the server in `src/main.rs`, so its modes can be
+//! compared without `wrk`.
+//!
+//! Each of `--connections` threads sends `GET` requests over one
+//! connection for `--duration` seconds, reusing it unless
+//! `--no-keep-alive` is given. Latencies go into a histogram, and the run
+//! is summed up as a markdown table on stdout and, with `--json`, as JSON.
+//!
+//! ```text
+//! cargo build --release
+//! target/release/loadgen --spawn all --connections 100 --duration 10
+//! target/release/loadgen --addr 127.0.0.1:8787 --path /sleep
+//! ```
+//!
+//! `--spawn` starts the server binary built beside this one in each mode
+//! in turn, on a free port, and stops it after its run.
+
+use std::{
+    env, fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    process::{self, Child, Command, Stdio},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+const USAGE: &str = "\
+usage: loadgen [--addr HOST:PORT | --spawn MODES] [--path PATH]
+               [--connections N] [--duration SECS] [--timeout SECS]
+               [--no-keep-alive] [--json FILE]
+
+  --addr         server to load (default: 127.0.0.1:8787)
+  --spawn        start the server in each of these modes, comma-separated,
+                 or `all`: single,pool,tokio,custom-runtime
+  --server-bin   server binary to spawn (default: rust-concurrency beside
+                 this binary)
+  --workers      --workers for spawned servers (default: 4)
+  --root         --root for spawned servers (default: public)
+  --path         request path (default: /)
+  --connections  concurrent connections (default: 100)
+  --duration     seconds to send requests for (default: 10)
+  --timeout      seconds to wait for a connection or response (default: 10)
+  --no-keep-alive
+                 open a new connection for every request
+  --json         also write the results as JSON to FILE";
+
+const MODES: [&str; 4] = ["single", "pool", "tokio", "custom-runtime"];
+
+#[derive(Debug)]
+struct Args {
+    addr: SocketAddr,
+    spawn: Vec<String>,
+    server_bin: Option<PathBuf>,
+    workers: usize,
+    root: PathBuf,
+    path: String,
+    connections: usize,
+    duration: Duration,
+    timeout: Duration,
+    keep_alive: bool,
+    json: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args {
+            addr: SocketAddr::from(([127, 0, 0, 1], 8787)),
+            spawn: Vec::new(),
+            server_bin: None,
+            workers: 4,
+            root: PathBuf::from("public"),
+            path: "/".to_string(),
+            connections: 100,
+            duration: Duration::from_secs(10),
+            timeout: Duration::from_secs(10),
+            keep_alive: true,
+            json: None,
+        };
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "-h" | "--help" => return Err(String::new()),
+                "--no-keep-alive" => {
+                    parsed.keep_alive = false;
+                    continue;
+                }
+                _ => {}
+            }
+            let value = args.next().ok_or_else(|| format!("{flag} needs a value"))?;
+            let invalid = |err: &dyn fmt::Display| format!("invalid {flag} {value:?}: {err}");
+            match flag.as_str() {
+                "--addr" => parsed.addr = value.parse().map_err(|err| invalid(&err))?,
+                "--spawn" if value == "all" => {
+                    parsed.spawn = MODES.iter().map(|mode| mode.to_string()).collect();
+                }
+                "--spawn" => {
+                    parsed.spawn = value.split(',').map(str::to_string).collect();
+                    let unknown = parsed.spawn.iter().find(|m| !MODES.contains(&m.as_str()));
+                    if let Some(mode) = unknown {
+                        return Err(invalid(&format!("unknown mode {mode:?}")));
+                    }
+                }
+                "--server-bin" => parsed.server_bin = Some(PathBuf::from(value)),
+                "--workers" => {
+                    parsed.workers = positive(&value).map_err(|err| invalid(&err))?;
+                }
+                "--root" => parsed.root = PathBuf::from(value),
+                "--path" if value.starts_with('/') => parsed.path = value,
+                "--path" => return Err(invalid(&"must start with /")),
+                "--connections" => {
+                    parsed.connections = positive(&value).map_err(|err| invalid(&err))?;
+                }
+                "--duration" => {
+                    parsed.duration = seconds(&value).map_err(|err| invalid(&err))?;
+                }
+                "--timeout" => parsed.timeout = seconds(&value).map_err(|err| invalid(&err))?,
+                "--json" => parsed.json = Some(PathBuf::from(value)),
+                _ => return Err(format!("unknown option {flag}")),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn positive<T: FromStr + Default + PartialEq>(value: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    match value.parse::<T>() {
+        Ok(n) if n == T::default() => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn seconds(value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("error: {err}");
+            }
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+    };
+    let reports = if args.spawn.is_empty() {
+        vec![load(&args, args.addr.to_string(), args.addr)]
+    } else {
+        let server_bin = args.server_bin.clone().unwrap_or_else(sibling_server);
+        let mut reports = Vec::new();
+        for mode in &args.spawn {
+            let server = match spawn(&server_bin, mode, &args) {
+                Ok(server) => server,
+                Err(e) => {
+                    eprintln!(
+                        "Error: cannot start {} in {mode} mode: {e}",
+                        server_bin.display()
+                    );
+                    process::exit(1);
+                }
+            };
+            reports.push(load(&args, mode.clone(), server.addr));
+            drop(server);
+        }
+        reports
+    };
+
+    println!("{}", markdown(&reports));
+    if let Some(path) = &args.json {
+        let results = json!({
+            "path": args.path,
+            "connections": args.connections,
+            "duration_secs": args.duration.as_secs_f64(),
+            "keep_alive": args.keep_alive,
+            "results": reports.iter().map(Report::to_json).collect::<Vec<_>>(),
+        });
+        let written = File::create(path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            serde_json::to_writer_pretty(&mut out, &results)?;
+            writeln!(out)?;
+            out.flush()
+        });
+        if let Err(e) = written {
+            eprintln!("Error: cannot write {}: {e}", path.display());
+            process::exit(1);
+        }
+    }
+}
+
+/// The server binary cargo builds into the same directory as this one.
+fn sibling_server() -> PathBuf {
+    let name = format!("rust-concurrency{}", env::consts::EXE_SUFFIX);
+    env::current_exe()
+        .map(|exe| exe.with_file_name(&name))
+        .unwrap_or_else(|_| PathBuf::from(name))
+}
+
+/// A server started by `--spawn`.
+struct Spawned {
+    child: Child,
+    addr: SocketAddr,
+}
+
+impl Drop for Spawned {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Starts the server in `mode` on a free port and waits until it accepts
+/// connections.
+fn spawn(server_bin: &PathBuf, mode: &str, args: &Args) -> io::Result<Spawned> {
+    // Binding port 0 finds a free port; it is released for the server.
+    let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    let child = Command::new(server_bin)
+        .arg("--mode")
+        .arg(mode)
+        .arg("--addr")
+        .arg(addr.to_string())
+        .arg("--workers")
+        .arg(args.workers.to_string())
+        .arg("--root")
+        .arg(&args.root)
+        .stdout(Stdio::null())
+        .spawn()?;
+    let mut server = Spawned { child, addr };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(status) = server.child.try_wait()? {
+            return Err(io::Error::other(format!("server exited with {status}")));
+        }
+        // A probe connection ties up the `single` server until it closes,
+        // which it does at once.
+        if TcpStream::connect(addr).is_ok() {
+            return Ok(server);
+        }
+        if Instant::now() >= deadline {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Runs the load against `addr` and collects what every connection saw.
+fn load(args: &Args, label: String, addr: SocketAddr) -> Report {
+    eprintln!(
+        "Loading {label}: {} connections to http://{addr}{} for {:?}",
+        args.connections, args.path, args.duration
+    );
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {addr}\r\nUser-Agent: loadgen\r\n{}\r\n",
+        args.path,
+        if args.keep_alive {
+            ""
+        } else {
+            "Connection: close\r\n"
+        }
+    );
+    let start = Instant::now();
+    let deadline = start + args.duration;
+    let connections: Vec<_> = (0..args.connections)
+        .map(|_| {
+            let request = request.clone();
+            let timeout = args.timeout;
+            thread::spawn(move || connection(addr, request.as_bytes(), timeout, deadline))
+        })
+        .collect();
+    let mut stats = Stats::default();
+    for handle in connections {
+        stats.merge(handle.join().expect("load thread panicked"));
+    }
+    Report {
+        label,
+        stats,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Counts from one or more connections.
+#[derive(Default)]
+struct Stats {
+    latency: Histogram,
+    /// Responses of any status.
+    responses: u64,
+    /// Bytes of response bodies.
+    bytes: u64,
+    connect_errors: u64,
+    /// Reads and writes that failed, and malformed responses.
+    io_errors: u64,
+    /// Responses that took longer than `--timeout`.
+    timeouts: u64,
+    /// `4xx` and `5xx` responses.
+    status_errors: u64,
+}
+
+impl Stats {
+    fn merge(&mut self, other: Stats) {
+        self.latency.merge(&other.latency);
+        self.responses += other.responses;
+        self.bytes += other.bytes;
+        self.connect_errors += other.connect_errors;
+        self.io_errors += other.io_errors;
+        self.timeouts += other.timeouts;
+        self.status_errors += other.status_errors;
+    }
+}
+
+/// Sends requests until `deadline`, reconnecting whenever the connection
+/// is closed or fails.
+fn connection(addr: SocketAddr, request: &[u8], timeout: Duration, deadline: Instant) -> Stats {
+    let mut stats = Stats::default();
+    let mut stream: Option<TcpStream> = None;
+    let mut buf = Vec::with_capacity(8 * 1024);
+    while Instant::now() < deadline {
+        let sent = Instant::now();
+        let conn = match stream.take() {
+            Some(conn) => conn,
+            None => match connect(addr, timeout) {
+                Ok(conn) => conn,
+                Err(_) => {
+                    stats.connect_errors += 1;
+                    // Keeps a refused connection from spinning.
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+            },
+        };
+        let exchanged = (&conn)
+            .write_all(request)
+            .and_then(|()| read_response(&conn, &mut buf));
+        match exchanged {
+            Ok(response) => {
+                stats.latency.record(sent.elapsed());
+                stats.responses += 1;
+                stats.bytes += response.body_len;
+                if response.status >= 400 {
+                    stats.status_errors += 1;
+                }
+                if response.keep_alive {
+                    stream = Some(conn);
+                }
+            }
+            Err(err) if is_timeout(&err) => stats.timeouts += 1,
+            Err(_) => stats.io_errors += 1,
+        }
+    }
+    stats
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    // An expired socket timeout is `WouldBlock` on Unix and `TimedOut` on
+    // Windows.
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn connect(addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+struct Response {
+    status: u16,
+    body_len: u64,
+    keep_alive: bool,
+}
+
+/// How the end of a response body is found.
+enum Framing {
+    Empty,
+    Length(u64),
+    Chunked,
+    /// Delimited by the server closing the connection.
+    Close,
+}
+
+/// Reads one response, discarding its body. Requests are not pipelined,
+/// so nothing follows it on the connection.
+fn read_response(mut stream: &TcpStream, buf: &mut Vec<u8>) -> io::Result<Response> {
+    buf.clear();
+    let mut chunk = [0; 8 * 1024];
+    let (head_len, status, framing, mut keep_alive) = loop {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut response = httparse::Response::new(&mut headers);
+        match response.parse(buf) {
+            Ok(httparse::Status::Complete(head_len)) => {
+                let header = |name: &str| {
+                    response
+                        .headers
+                        .iter()
+                        .find(|h| h.name.eq_ignore_ascii_case(name))
+                        .map(|h| String::from_utf8_lossy(h.value).into_owned())
+                };
+                let status = response.code.unwrap_or(0);
+                // `chunked` is always the last coding, and overrides any
+                // `Content-Length`.
+                let chunked = header("transfer-encoding").is_some_and(|codings| {
+                    let last = codings.rsplit(',').next().unwrap_or_default();
+                    last.trim().eq_ignore_ascii_case("chunked")
+                });
+                let framing = if status == 204 || status == 304 || status < 200 {
+                    Framing::Empty
+                } else if chunked {
+                    Framing::Chunked
+                } else if let Some(len) = header("content-length") {
+                    let len = len.trim().parse::<u64>();
+                    Framing::Length(len.map_err(|_| invalid("bad Content-Length"))?)
+                } else {
+                    Framing::Close
+                };
+                let close =
+                    header("connection").is_some_and(|value| value.eq_ignore_ascii_case("close"));
+                break (head_len, status, framing, !close);
+            }
+            Ok(httparse::Status::Partial) => {}
+            Err(e) => return Err(invalid(&e.to_string())),
+        }
+    };
+    let read = &buf[head_len..];
+    let body_len = match framing {
+        Framing::Empty => 0,
+        Framing::Length(len) => {
+            let mut left = len.saturating_sub(read.len() as u64);
+            while left > 0 {
+                let want = left.min(chunk.len() as u64) as usize;
+                let n = stream.read(&mut chunk[..want])?;
+                if n == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                left -= n as u64;
+            }
+            len
+        }
+        Framing::Chunked => read_chunked(BufReader::new(read.chain(stream)))?,
+        Framing::Close => {
+            keep_alive = false;
+            let mut len = read.len() as u64;
+            loop {
+                match stream.read(&mut chunk)? {
+                    0 => break len,
+                    n => len += n as u64,
+                }
+            }
+        }
+    };
+    Ok(Response {
+        status,
+        body_len,
+        keep_alive,
+    })
+}
+
+/// Reads a chunked body up to and including its trailers, and returns its
+/// decoded length.
+fn read_chunked(mut body: impl BufRead) -> io::Result<u64> {
+    let mut line = String::new();
+    let mut next_line = |body: &mut dyn BufRead| {
+        line.clear();
+        match body.read_line(&mut line)? {
+            0 => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+            _ => Ok(line.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    };
+    let mut len = 0u64;
+    loop {
+        let size = next_line(&mut body)?;
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size, 16).map_err(|_| invalid("bad chunk size"))?;
+        if size == 0 {
+            // Trailers, up to an empty line.
+            while !next_line(&mut body)?.is_empty() {}
+            return Ok(len);
+        }
+        let copied = io::copy(&mut (&mut body).take(size), &mut io::sink())?;
+        if copied < size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if !next_line(&mut body)?.is_empty() {
+            return Err(invalid("chunk is longer than its size"));
+        }
+        len = len.saturating_add(size);
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Latencies in microseconds, in buckets about 1.5% wide.
+///
+/// Values below 128 get a bucket each; above that, every power of two is
+/// split into 64 buckets.
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    max: u64,
+}
+
+const EXACT: u64 = 128;
+const SUB_BUCKETS: u64 = 64;
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; Self::index(u64::MAX) + 1],
+            total: 0,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn index(micros: u64) -> usize {
+        if micros < EXACT {
+            return micros as usize;
+        }
+        // `micros >> shift` falls in 64..128.
+        let shift = u64::from(63 - micros.leading_zeros()) - 6;
+        (EXACT + (shift - 1) * SUB_BUCKETS + (micros >> shift) - SUB_BUCKETS) as usize
+    }
+
+    /// The smallest value in bucket `index`.
+    fn lowest(index: usize) -> u64 {
+        let index = index as u64;
+        if index < EXACT {
+            return index;
+        }
+        let shift = (index - EXACT) / SUB_BUCKETS + 1;
+        ((index - EXACT) % SUB_BUCKETS + SUB_BUCKETS) << shift
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.counts[Self::index(micros)] += 1;
+        self.total += 1;
+        self.max = self.max.max(micros);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+
+    /// The latency `quantile` (0 to 1) of recorded values are at or below,
+    /// to within a bucket.
+    fn quantile(&self, quantile: f64) -> Duration {
+        if self.total == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((quantile * self.total as f64).ceil() as u64).clamp(1, self.total);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                // The middle of the bucket, never above what was seen.
+                let low = Self::lowest(index);
+                let high = Self::lowest(index + 1).wrapping_sub(1);
+                return Duration::from_micros((low + (high - low) / 2).min(self.max));
+            }
+        }
+        Duration::from_micros(self.max)
+    }
+
+    fn max(&self) -> Duration {
+        Duration::from_micros(self.max)
+    }
+}
+
+/// The outcome of loading one server.
+struct Report {
+    label: String,
+    stats: Stats,
+    elapsed: Duration,
+}
+
+impl Report {
+    fn throughput(&self) -> f64 {
+        self.stats.responses as f64 / self.elapsed.as_secs_f64()
+    }
+
+    fn errors(&self) -> u64 {
+        self.stats.connect_errors
+            + self.stats.io_errors
+            + self.stats.timeouts
+            + self.stats.status_errors
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let ms = |latency: Duration| latency.as_micros() as f64 / 1000.0;
+        let latency = &self.stats.latency;
+        json!({
+            "server": self.label,
+            "elapsed_secs": self.elapsed.as_secs_f64(),
+            "requests": self.stats.responses,
+            "requests_per_sec": self.throughput(),
+            "bytes": self.stats.bytes,
+            "latency_ms": {
+                "p50": ms(latency.quantile(0.50)),
+                "p90": ms(latency.quantile(0.90)),
+                "p99": ms(latency.quantile(0.99)),
+                "max": ms(latency.max()),
+            },
+            "errors": {
+                "connect": self.stats.connect_errors,
+                "io": self.stats.io_errors,
+                "timeout": self.stats.timeouts,
+                "status": self.stats.status_errors,
+            },
+        })
+    }
+}
+
+fn markdown(reports: &[Report]) -> String {
+    let ms = |latency: Duration| format!("{:.2}", latency.as_secs_f64() * 1000.0);
+    let mut table = String::from(
+        "| Server | Requests | Req/s | p50 (ms) | p90 (ms) | p99 (ms) | Max (ms) | Errors |\n\
+         | :--- | ---: | ---: | ---: | ---: | ---: | ---: | ---: |\n",
+    );
+    for report in reports {
+        let latency = &report.stats.latency;
+        table += &format!(
+            "| {} | {} | {:.0} | {} | {} | {} | {} | {} |\n",
+            report.label,
+            report.stats.responses,
+            report.throughput(),
+            ms(latency.quantile(0.50)),
+            ms(latency.quantile(0.90)),
+            ms(latency.quantile(0.99)),
+            ms(latency.max()),
+            report.errors(),
+        );
+    }
+    table
+}
+
+#[test]
+fn histogram_buckets_are_contiguous() {
+    for micros in (0..100_000).chain([u64::MAX / 3, u64::MAX]) {
+        let index = Histogram::index(micros);
+        assert!(Histogram::lowest(index) <= micros, "{micros}");
+        if index + 1 < Histogram::default().counts.len() {
+            assert!(Histogram::lowest(index + 1) > micros, "{micros}");
+        }
+    }
+}
+
+#[test]
+fn histogram_quantiles_are_close() {
+    let mut histogram = Histogram::default();
+    for micros in 1..=10_000 {
+        histogram.record(Duration::from_micros(micros));
+    }
+    for (quantile, expected) in [(0.5, 5_000.0), (0.9, 9_000.0), (0.99, 9_900.0)] {
+        let got = histogram.quantile(quantile).as_micros() as f64;
+        assert!(
+            (got - expected).abs() / expected < 0.02,
+            "{quantile}: {got}"
+        );
+    }
+    assert_eq!(histogram.max(), Duration::from_micros(10_000));
+    assert_eq!(Histogram::default().quantile(0.5), Duration::ZERO);
+}
+
+#[test]
+fn args_are_parsed() {
+    let args = |list: &[&str]| Args::parse(list.iter().map(|s| s.to_string()));
+    let parsed = args(&[]).unwrap();
+    assert_eq!((parsed.connections, parsed.path.as_str()), (100, "/"));
+    assert!(parsed.spawn.is_empty() && parsed.keep_alive);
+    let parsed = args(&["--spawn", "all", "--duration", "0.5", "--no-keep-alive"]).unwrap();
+    assert_eq!(parsed.spawn, MODES);
+    assert_eq!(parsed.duration, Duration::from_millis(500));
+    assert!(!parsed.keep_alive);
+    assert!(args(&["--spawn", "pool,fibers"]).is_err());
+    assert!(args(&["--connections", "0"]).is_err());
+    assert!(args(&["--path", "sleep"]).is_err());
+}
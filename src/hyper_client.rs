@@ -1,147 +0,0 @@
-// below is the abstracted runtime
-use anyhow::{bail, Context as _, Error, Result};
-use async_native_tls::TlsStream;
-use futures_lite::future;
-use http::Uri;
-use hyper::{Body, Client, Request, Response};
-use rust_concurrency::runtime::{spawn_task_function, FutureType, Runtime};
-use smol::{io, prelude::*, Async};
-use std::net::Shutdown;
-use std::net::{TcpStream, ToSocketAddrs};
-use std::pin::Pin;
-use std::task::{Context, Poll};
-
-use rust_concurrency::spawn_task;
-
-enum CustomStream {
-    Plain(Async<TcpStream>),
-    Tls(TlsStream<Async<TcpStream>>),
-}
-
-impl tokio::io::AsyncRead for CustomStream {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut tokio::io::ReadBuf<'_>,
-    ) -> Poll<std::io::Result<()>> {
-        match &mut *self {
-            CustomStream::Plain(s) => {
-                Pin::new(s)
-                    .poll_read(cx, buf.initialize_unfilled())
-                    .map_ok(|size| {
-                        buf.advance(size);
-                    })
-            }
-            CustomStream::Tls(s) => {
-                Pin::new(s)
-                    .poll_read(cx, buf.initialize_unfilled())
-                    .map_ok(|size| {
-                        buf.advance(size);
-                    })
-            }
-        }
-    }
-}
-
-impl tokio::io::AsyncWrite for CustomStream {
-    fn poll_write(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<io::Result<usize>> {
-        match &mut *self {
-            CustomStream::Plain(s) => Pin::new(s).poll_write(cx, buf),
-            CustomStream::Tls(s) => Pin::new(s).poll_write(cx, buf),
-        }
-    }
-
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match &mut *self {
-            CustomStream::Plain(s) => Pin::new(s).poll_flush(cx),
-            CustomStream::Tls(s) => Pin::new(s).poll_flush(cx),
-        }
-    }
-
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        match &mut *self {
-            CustomStream::Plain(s) => {
-                s.get_ref().shutdown(Shutdown::Write)?;
-                Poll::Ready(Ok(()))
-            }
-            CustomStream::Tls(s) => Pin::new(s).poll_close(cx),
-        }
-    }
-}
-
-impl hyper::client::connect::Connection for CustomStream {
-    fn connected(&self) -> hyper::client::connect::Connected {
-        hyper::client::connect::Connected::new()
-    }
-}
-
-async fn fetch(req: Request<Body>) -> Result<Response<Body>> {
-    Ok(Client::builder()
-        .executor(CustomExecutor)
-        .build::<_, Body>(CustomConnector)
-        .request(req)
-        .await?)
-}
-
-#[derive(Clone)]
-struct CustomConnector;
-
-impl hyper::service::Service<Uri> for CustomConnector {
-    type Response = CustomStream;
-    type Error = Error;
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
-
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
-    }
-
-    fn call(&mut self, uri: Uri) -> Self::Future {
-        Box::pin(async move {
-            let host = uri.host().context("cannot parse host")?;
-            match uri.scheme_str() {
-                Some("http") => {
-                    let socket_addr = {
-                        let host = host.to_string();
-                        let port = uri.port_u16().unwrap_or(80);
-                        smol::unblock(move || (host.as_str(), port).to_socket_addrs())
-                            .await?
-                            .next()
-                            .context("cannot resolve address")?
-                    };
-                    let stream = Async::<TcpStream>::connect(socket_addr).await?;
-                    Ok(CustomStream::Plain(stream))
-                }
-                Some("https") => {
-                    let socket_addr = {
-                        let host = host.to_string();
-                        let port = uri.port_u16().unwrap_or(443);
-                        smol::unblock(move || (host.as_str(), port).to_socket_addrs())
-                            .await?
-                            .next()
-                            .context("cannot resolve address")?
-                    };
-                    let stream = Async::<TcpStream>::connect(socket_addr).await?;
-                    let stream = async_native_tls::connect(host, stream).await?;
-                    Ok(CustomStream::Tls(stream))
-                }
-                scheme => bail!("unsupported scheme: {:?}", scheme),
-            }
-        })
-    }
-}
-
-struct CustomExecutor;
-
-impl<F: Future + Send + 'static> hyper::rt::Executor<F> for CustomExecutor {
-    fn execute(&self, future: F) {
-        spawn_task!(async {
-            println!("sending request");
-            future.await;
-        })
-        .detach();
-    }
-}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::Stream;
+use http::Uri;
+use hyper::body::Bytes;
+use hyper::{Body, Request, Response};
+
+use super::{default_client, Client};
+use crate::runtime::{sleep, spawn_task_function, FutureType, JoinHandle};
+use crate::spawn_task;
+use crate::sync::Semaphore;
+
+/// Fetches many requests concurrently on the runtime, within limits on
+/// concurrency and rate, and streams the results back as they finish.
+///
+/// Each request runs as its own task. It waits for a slot for its host,
+/// then for one of the overall slots, then for a token from the rate
+/// limiter, and keeps its slots until its whole body has been read and
+/// handed to the stream.
+#[derive(Clone)]
+pub struct BatchFetch {
+    client: Client,
+    max_in_flight: usize,
+    max_per_host: usize,
+    rate: Option<(f64, u32)>,
+}
+
+impl BatchFetch {
+    /// Uses the same client as [`fetch`](super::fetch), with at most 64
+    /// requests in flight, 6 per host, and no rate limit.
+    pub fn new() -> Self {
+        Self {
+            client: default_client().clone(),
+            max_in_flight: 64,
+            max_per_host: 6,
+            rate: None,
+        }
+    }
+
+    pub fn client(mut self, client: &Client) -> Self {
+        self.client = client.clone();
+        self
+    }
+
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = max.max(1);
+        self
+    }
+
+    /// Limit per `host:port`.
+    pub fn max_per_host(mut self, max: usize) -> Self {
+        self.max_per_host = max.max(1);
+        self
+    }
+
+    /// Starts at most `per_second` requests a second on average, letting up
+    /// to `burst` go at once after a quiet spell.
+    pub fn rate_limit(mut self, per_second: f64, burst: u32) -> Self {
+        assert!(per_second > 0.0, "rate limit must be positive");
+        self.rate = Some((per_second, burst.max(1)));
+        self
+    }
+
+    /// Spawns a task per request and returns their results in completion
+    /// order. Dropping the stream cancels whatever has not finished.
+    pub fn send(&self, requests: impl IntoIterator<Item = Request<Body>>) -> BatchStream {
+        let global = Arc::new(Semaphore::new(self.max_in_flight));
+        let bucket = self
+            .rate
+            .map(|(rate, burst)| Arc::new(TokenBucket::new(rate, burst)));
+        let mut hosts: HashMap<String, Arc<Semaphore>> = HashMap::new();
+        // Bounded, so a slow consumer holds back new requests instead of
+        // piling up bodies in memory.
+        let (sender, receiver) = flume::bounded(self.max_in_flight);
+
+        let tasks = requests
+            .into_iter()
+            .enumerate()
+            .map(|(index, req)| {
+                let host = hosts
+                    .entry(host_key(req.uri()))
+                    .or_insert_with(|| Arc::new(Semaphore::new(self.max_per_host)))
+                    .clone();
+                let (client, global, bucket, sender) = (
+                    self.client.clone(),
+                    global.clone(),
+                    bucket.clone(),
+                    sender.clone(),
+                );
+                spawn_task!(async move {
+                    let _host = host.acquire_owned().await.expect("semaphore closed");
+                    let _slot = global.acquire_owned().await.expect("semaphore closed");
+                    if let Some(bucket) = bucket {
+                        bucket.acquire().await;
+                    }
+                    let uri = req.uri().clone();
+                    let result = fetch_bytes(&client, req).await;
+                    let _ = sender.send_async(BatchResult { index, uri, result }).await;
+                })
+            })
+            .collect();
+
+        BatchStream {
+            results: receiver.into_stream(),
+            _tasks: tasks,
+        }
+    }
+
+    /// [`send`](Self::send)s a `GET` for each of `uris`.
+    pub fn get(&self, uris: impl IntoIterator<Item = Uri>) -> BatchStream {
+        self.send(uris.into_iter().map(|uri| {
+            let mut req = Request::new(Body::empty());
+            *req.uri_mut() = uri;
+            req
+        }))
+    }
+}
+
+impl Default for BatchFetch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One finished request of a [`BatchFetch`].
+#[derive(Debug)]
+pub struct BatchResult {
+    /// Position of the request in the batch.
+    pub index: usize,
+    pub uri: Uri,
+    /// The response with its body read in full, within the client's
+    /// maximum body size.
+    pub result: Result<Response<Bytes>>,
+}
+
+/// Results of a [`BatchFetch`], in the order the requests finish.
+pub struct BatchStream {
+    results: flume::r#async::RecvStream<'static, BatchResult>,
+    _tasks: Vec<JoinHandle<()>>,
+}
+
+impl Stream for BatchStream {
+    type Item = BatchResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<BatchResult>> {
+        Pin::new(&mut self.results).poll_next(cx)
+    }
+}
+
+async fn fetch_bytes(client: &Client, req: Request<Body>) -> Result<Response<Bytes>> {
+    let (parts, body) = client.stream(req).await?.into_parts();
+    Ok(Response::from_parts(parts, body.bytes().await?))
+}
+
+fn host_key(uri: &Uri) -> String {
+    let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+        Some("https") => 443,
+        _ => 80,
+    });
+    format!("{}:{port}", uri.host().unwrap_or_default())
+}
+
+/// Hands out tokens at `rate` a second, holding at most `burst`.
+///
+/// Callers reserve a token up front, letting the balance go negative, and
+/// sleep until it would have been refilled. That queues waiters in order
+/// without waking them all at every refill.
+pub(crate) struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: burst.into(),
+            state: Mutex::new((burst.into(), Instant::now())),
+        }
+    }
+
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let (tokens, last) = &mut *state;
+            let now = Instant::now();
+            *tokens =
+                (*tokens + now.duration_since(*last).as_secs_f64() * self.rate).min(self.burst);
+            *last = now;
+            *tokens -= 1.0;
+            (*tokens < 0.0).then(|| Duration::from_secs_f64(-*tokens / self.rate))
+        };
+        if let Some(wait) = wait {
+            sleep(wait).await;
+        }
+    }
+}
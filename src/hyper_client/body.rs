@@ -0,0 +1,127 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::Result;
+use futures::stream::{IntoAsyncRead, Stream, StreamExt, TryStreamExt};
+use hyper::body::{Bytes, HttpBody};
+use hyper::Body;
+
+use super::error::find_timeout;
+use super::TimeoutError;
+
+/// Returned when a body grows past the client's maximum body size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyTooLarge {
+    pub limit: u64,
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "body exceeds the limit of {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// A response body read chunk by chunk, without buffering it whole.
+///
+/// Yields `io::Error`s so it can be turned into an `AsyncRead` with
+/// [`into_async_read`](Self::into_async_read); [`TimeoutError`] and
+/// [`BodyTooLarge`] can be recovered from them with `get_ref`.
+pub struct BodyStream {
+    body: Body,
+    received: u64,
+    limit: Option<u64>,
+}
+
+impl BodyStream {
+    pub fn new(body: Body) -> Self {
+        Self::with_limit(body, None)
+    }
+
+    /// Fails the stream once more than `limit` bytes have arrived.
+    pub fn max_size(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub(crate) fn with_limit(body: Body, limit: Option<u64>) -> Self {
+        Self {
+            body,
+            received: 0,
+            limit,
+        }
+    }
+
+    /// Bytes received so far.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Reads the rest of the body into memory, still within the limit.
+    pub async fn bytes(mut self) -> Result<Bytes> {
+        let mut buf = Vec::new();
+        while let Some(chunk) = self.next().await {
+            buf.extend_from_slice(&chunk.map_err(into_anyhow)?);
+        }
+        Ok(buf.into())
+    }
+
+    /// Reads the rest of the body as UTF-8 text, failing rather than
+    /// panicking on binary data.
+    pub async fn text(self) -> Result<String> {
+        Ok(String::from_utf8(self.bytes().await?.to_vec())?)
+    }
+
+    pub fn into_async_read(self) -> IntoAsyncRead<Self> {
+        TryStreamExt::into_async_read(self)
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = match std::task::ready!(Pin::new(&mut self.body).poll_data(cx)) {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(err)) => {
+                return Poll::Ready(Some(Err(match find_timeout(&err) {
+                    Some(timeout) => io::Error::new(io::ErrorKind::TimedOut, timeout),
+                    None => io::Error::other(err),
+                })));
+            }
+            None => return Poll::Ready(None),
+        };
+        self.received += chunk.len() as u64;
+        if let Some(limit) = self.limit.filter(|&limit| self.received > limit) {
+            return Poll::Ready(Some(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                BodyTooLarge { limit },
+            ))));
+        }
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyStream")
+            .field("received", &self.received)
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+/// Unwraps the errors [`BodyStream`] wraps in `io::Error` so callers can
+/// downcast them directly.
+pub(crate) fn into_anyhow(err: io::Error) -> anyhow::Error {
+    if let Some(too_large) = err.get_ref().and_then(|e| e.downcast_ref::<BodyTooLarge>()) {
+        return (*too_large).into();
+    }
+    if let Some(timeout) = err.get_ref().and_then(|e| e.downcast_ref::<TimeoutError>()) {
+        return (*timeout).into();
+    }
+    err.into()
+}
@@ -0,0 +1,417 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{ensure, Result};
+use http::header::{self, HeaderMap, HeaderValue};
+use http::request::Parts;
+use http::Uri;
+use hyper::body::Bytes;
+use hyper::{Body, Request, Response};
+
+use super::body::BodyStream;
+use super::connector::CustomConnector;
+use super::cookie::CookieJar;
+use super::decompress::{self, ACCEPT_ENCODING};
+use super::download::Download;
+use super::error::find_timeout;
+use super::proxy::{ProxyConfig, ProxyKind};
+use super::redirect::{self, RedirectPolicy};
+use super::retry::{is_retryable_status, retry_after};
+use super::{
+    BodyTooLarge, CustomExecutor, Resolve, RetryPolicy, SystemResolver, TimeoutError, TlsConfig,
+    WithTimer,
+};
+use crate::runtime::{sleep, timeout};
+
+/// A long-lived HTTP client that keeps connections open and reuses them for
+/// later requests to the same host.
+///
+/// Cloning is cheap and clones share one connection pool, so build a client
+/// once and hand it out.
+#[derive(Clone)]
+pub struct Client {
+    inner: hyper::Client<CustomConnector, Body>,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    redirect: RedirectPolicy,
+    decompress: bool,
+    proxies: Arc<ProxyConfig>,
+    pub(crate) max_body_size: Option<u64>,
+    /// Set by [`Session`](super::Session), which shares this client's pool.
+    pub(crate) cookies: Option<Arc<CookieJar>>,
+    pub(crate) default_headers: Arc<HeaderMap>,
+}
+
+impl Client {
+    /// A client with the default pool settings; see [`ClientBuilder`].
+    pub fn new() -> Self {
+        ClientBuilder::new().build()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Sends `req`, retrying it and following redirects as the client's
+    /// [`RetryPolicy`] and [`RedirectPolicy`] allow.
+    ///
+    /// Timeouts surface as errors that downcast to [`TimeoutError`], and
+    /// refused redirects as [`RedirectError`](super::RedirectError)s.
+    pub async fn request(&self, mut req: Request<Body>) -> Result<Response<Body>> {
+        for name in self.default_headers.keys() {
+            if !req.headers().contains_key(name) {
+                for value in self.default_headers.get_all(name) {
+                    req.headers_mut().append(name, value.clone());
+                }
+            }
+        }
+        // A caller that picks its own encodings gets the bytes as sent.
+        let decode = self.decompress && !req.headers().contains_key(header::ACCEPT_ENCODING);
+        if decode {
+            req.headers_mut()
+                .insert(header::ACCEPT_ENCODING, ACCEPT_ENCODING);
+        }
+        let send = self.follow_redirects(req);
+        let response = match self.timeout {
+            Some(duration) => timeout(duration, send)
+                .await
+                .map_err(|_| TimeoutError::Total)?,
+            None => send.await,
+        }?;
+        Ok(if decode {
+            decompress::decode(response)
+        } else {
+            response
+        })
+    }
+
+    pub async fn get(&self, uri: Uri) -> Result<Response<Body>> {
+        self.request(Request::get(uri).body(Body::empty())?).await
+    }
+
+    /// Sends `req` and hands back the body as a [`BodyStream`] held to the
+    /// client's maximum body size. A response that announces a larger
+    /// `Content-Length` fails here, before any of it is read.
+    pub async fn stream(&self, req: Request<Body>) -> Result<Response<BodyStream>> {
+        let response = self.request(req).await?;
+        if let Some(limit) = self.max_body_size {
+            let length = response
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+            ensure!(
+                length.is_none_or(|length| length <= limit),
+                BodyTooLarge { limit }
+            );
+        }
+        Ok(response.map(|body| BodyStream::with_limit(body, self.max_body_size)))
+    }
+
+    /// Prepares a download of `uri` into the file at `path`; see
+    /// [`Download`].
+    pub fn download(&self, uri: Uri, path: impl AsRef<Path>) -> Download<'_> {
+        Download::new(self, uri, path.as_ref().to_path_buf())
+    }
+
+    async fn follow_redirects(&self, req: Request<Body>) -> Result<Response<Body>> {
+        if !self.redirect.follows() {
+            return self.send_with_retries(req).await;
+        }
+        // Redirects that keep the method resend the body.
+        let (mut parts, body) = req.into_parts();
+        let mut body = hyper::body::to_bytes(body).await?;
+        let origin = parts.uri.clone();
+        let mut hops = 0;
+        loop {
+            let response = self
+                .send_with_retries(rebuild(&parts, body.clone()))
+                .await?;
+            let Some(location) = redirect::location(&parts.uri, &response)? else {
+                return Ok(response);
+            };
+            self.redirect.check(hops, &origin, &location)?;
+            tracing::debug!(from = %parts.uri, to = %location, "following redirect");
+            redirect::redirect(&mut parts, &mut body, response.status(), location);
+            hops += 1;
+        }
+    }
+
+    async fn send_with_retries(&self, req: Request<Body>) -> Result<Response<Body>> {
+        if !self.retry.applies_to(req.method()) {
+            return self.send(req).await;
+        }
+        // Every attempt needs its own copy of the body.
+        let (parts, body) = req.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let mut attempt = 0;
+        loop {
+            let result = self.send(rebuild(&parts, body.clone())).await;
+            if attempt == self.retry.max_retries() {
+                return result;
+            }
+            let retry_after = match &result {
+                Ok(response) if is_retryable_status(response.status()) => retry_after(response),
+                Err(err) if is_connect_error(err) => None,
+                _ => return result,
+            };
+            let delay = self.retry.backoff(attempt, retry_after);
+            tracing::debug!(
+                uri = %parts.uri,
+                attempt = attempt + 1,
+                ?delay,
+                "retrying request"
+            );
+            drop(result);
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send(&self, mut req: Request<Body>) -> Result<Response<Body>> {
+        if let Some(auth) = self.forward_proxy_auth(req.uri()) {
+            req.headers_mut()
+                .entry(header::PROXY_AUTHORIZATION)
+                .or_insert(auth);
+        }
+        let Some(jar) = &self.cookies else {
+            return self.send_raw(req).await;
+        };
+        // Asked for each attempt and hop, so cookies set by an earlier
+        // redirect go out with the next one.
+        if let Some(cookies) = jar.header(req.uri()) {
+            let cookies = match req.headers().get(header::COOKIE) {
+                Some(own) => {
+                    let mut merged = own.as_bytes().to_vec();
+                    merged.extend_from_slice(b"; ");
+                    merged.extend_from_slice(cookies.as_bytes());
+                    HeaderValue::from_bytes(&merged)?
+                }
+                None => cookies,
+            };
+            req.headers_mut().insert(header::COOKIE, cookies);
+        }
+        let uri = req.uri().clone();
+        let response = self.send_raw(req).await?;
+        jar.store_response(&uri, response.headers());
+        Ok(response)
+    }
+
+    async fn send_raw(&self, req: Request<Body>) -> Result<Response<Body>> {
+        WithTimer(self.inner.request(req))
+            .await
+            .map_err(|err| match find_timeout(&err) {
+                Some(timeout) => timeout.into(),
+                None => err.into(),
+            })
+    }
+
+    /// Credentials for a plain request sent to a forwarding HTTP proxy.
+    /// Tunnelled requests authenticate in the `CONNECT` instead.
+    fn forward_proxy_auth(&self, uri: &Uri) -> Option<HeaderValue> {
+        if uri.scheme_str() != Some("http") {
+            return None;
+        }
+        let proxy = self.proxies.proxy_for("http", uri.host()?)?;
+        match proxy.kind {
+            ProxyKind::Http => proxy.basic_auth(),
+            ProxyKind::Socks5 => None,
+        }
+    }
+}
+
+fn rebuild(parts: &Parts, body: Bytes) -> Request<Body> {
+    let mut req = Request::new(Body::from(body));
+    *req.method_mut() = parts.method.clone();
+    *req.uri_mut() = parts.uri.clone();
+    *req.version_mut() = parts.version;
+    *req.headers_mut() = parts.headers.clone();
+    req
+}
+
+fn is_connect_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<TimeoutError>() == Some(&TimeoutError::Connect)
+        || err
+            .downcast_ref::<hyper::Error>()
+            .is_some_and(hyper::Error::is_connect)
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configures the connection pool, timeouts and retries of a [`Client`].
+#[derive(Clone)]
+pub struct ClientBuilder {
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Option<Duration>,
+    max_connections_per_host: Option<usize>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    redirect: RedirectPolicy,
+    decompress: bool,
+    http2: bool,
+    resolver: Arc<dyn Resolve>,
+    proxies: ProxyConfig,
+    tls: TlsConfig,
+    max_body_size: Option<u64>,
+}
+
+impl ClientBuilder {
+    /// Unlimited idle connections kept for 90 seconds, no cap on open
+    /// connections per host, no timeouts, no retries, no redirects, no
+    /// decompression and no proxy. HTTP/2 is used whenever a TLS server
+    /// offers it.
+    pub fn new() -> Self {
+        Self {
+            pool_max_idle_per_host: usize::MAX,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            max_connections_per_host: None,
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            retry: RetryPolicy::none(),
+            redirect: RedirectPolicy::none(),
+            decompress: false,
+            http2: true,
+            resolver: Arc::new(SystemResolver),
+            proxies: ProxyConfig::none(),
+            tls: TlsConfig::new(),
+            max_body_size: None,
+        }
+    }
+
+    /// How many idle connections to keep per host. Extra connections are
+    /// closed when their request finishes; `0` disables reuse.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
+    /// How long an idle connection stays in the pool. `None` keeps idle
+    /// connections until the server closes them.
+    pub fn pool_idle_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.pool_idle_timeout = timeout.into();
+        self
+    }
+
+    /// Caps the number of connections open to one `host:port` at a time.
+    /// Requests beyond that wait for a connection to become free.
+    pub fn max_connections_per_host(mut self, max: usize) -> Self {
+        self.max_connections_per_host = Some(max.max(1));
+        self
+    }
+
+    /// Limit on resolving, connecting and the TLS handshake.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit on how long a connection may go without receiving data while
+    /// the client waits for it. This also closes pooled connections that
+    /// stay idle for longer.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit on the whole request, retries and backoff included, up to the
+    /// arrival of the response headers.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    pub fn redirect(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect = policy;
+        self
+    }
+
+    /// Ask for gzip, deflate or brotli bodies and decode them, so
+    /// responses read as if they had been sent uncompressed. Requests that
+    /// set their own `Accept-Encoding` are left alone.
+    pub fn decompress(mut self, decompress: bool) -> Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Only ever speak HTTP/1.1, even to servers that offer HTTP/2.
+    pub fn http1_only(mut self) -> Self {
+        self.http2 = false;
+        self
+    }
+
+    /// How host names are turned into addresses; the [`SystemResolver`]
+    /// by default.
+    pub fn resolver(mut self, resolver: impl Resolve) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Which proxies to use; [`ProxyConfig::from_env`] follows the usual
+    /// environment variables.
+    pub fn proxy(mut self, proxies: ProxyConfig) -> Self {
+        self.proxies = proxies;
+        self
+    }
+
+    pub fn tls(mut self, config: TlsConfig) -> Self {
+        self.tls = config;
+        self
+    }
+
+    /// Largest body, in bytes, that [`Client::stream`] and
+    /// [`Client::download`] accept. Bodies are unlimited by default.
+    pub fn max_body_size(mut self, limit: u64) -> Self {
+        self.max_body_size = Some(limit);
+        self
+    }
+
+    /// Builds the client. TLS settings are checked on the first `https`
+    /// connection, which fails if they are unusable.
+    pub fn build(self) -> Client {
+        let proxies = Arc::new(self.proxies);
+        let connector = CustomConnector {
+            max_per_host: self.max_connections_per_host,
+            connect_timeout: self.connect_timeout,
+            read_timeout: self.read_timeout,
+            http2: self.http2,
+            resolver: self.resolver,
+            proxies: proxies.clone(),
+            tls: Arc::new(self.tls),
+            ..CustomConnector::default()
+        };
+        let inner = hyper::Client::builder()
+            .executor(CustomExecutor)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .build(connector);
+        Client {
+            inner,
+            timeout: self.timeout,
+            retry: self.retry,
+            redirect: self.redirect,
+            decompress: self.decompress,
+            proxies,
+            max_body_size: self.max_body_size,
+            cookies: None,
+            default_headers: Arc::default(),
+        }
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
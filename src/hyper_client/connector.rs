@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use anyhow::{bail, Context as _, Result};
+use http::Uri;
+use hyper::client::connect::{Connected, Connection};
+use once_cell::sync::OnceCell;
+use smol::{io, prelude::*, Async};
+use tokio_native_tls::TlsStream;
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt};
+
+use super::proxy::{ProxyConfig, ProxyKind};
+use super::resolve::{connect_any, Resolve, SystemResolver};
+use super::tls::TlsConfig;
+use super::TimeoutError;
+use crate::runtime::{sleep, timeout, Sleep};
+use crate::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub(crate) struct CustomStream {
+    io: StreamIo,
+    info: ConnectionInfo,
+    /// Counts this connection against its host's limit until it closes.
+    _permit: Option<OwnedSemaphorePermit>,
+    read_timeout: Option<Duration>,
+    /// Armed while a read is pending; reset whenever data arrives.
+    read_deadline: Option<Sleep>,
+}
+
+enum StreamIo {
+    Plain(Async<TcpStream>),
+    Tls(TlsStream<Compat<Async<TcpStream>>>),
+}
+
+/// Details of the connection a response arrived on, available from the
+/// response's extensions.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    remote_addr: SocketAddr,
+    local_addr: SocketAddr,
+    http2: bool,
+    proxied: bool,
+    /// Sent to an HTTP proxy that forwards requests, not through a tunnel.
+    forwarded: bool,
+}
+
+impl ConnectionInfo {
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Whether the server agreed to HTTP/2 during the TLS handshake.
+    pub fn is_http2(&self) -> bool {
+        self.http2
+    }
+
+    /// Whether the connection goes through a proxy, in which case
+    /// [`remote_addr`](Self::remote_addr) is the proxy's address.
+    pub fn is_proxied(&self) -> bool {
+        self.proxied
+    }
+}
+
+impl tokio::io::AsyncRead for CustomStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        let poll = match &mut this.io {
+            StreamIo::Plain(s) => {
+                Pin::new(s)
+                    .poll_read(cx, buf.initialize_unfilled())
+                    .map_ok(|size| {
+                        buf.advance(size);
+                    })
+            }
+            StreamIo::Tls(s) => tokio::io::AsyncRead::poll_read(Pin::new(s), cx, buf),
+        };
+        if poll.is_ready() {
+            this.read_deadline = None;
+            return poll;
+        }
+        let Some(read_timeout) = this.read_timeout else {
+            return Poll::Pending;
+        };
+        let deadline = this
+            .read_deadline
+            .get_or_insert_with(|| sleep(read_timeout));
+        match Pin::new(deadline).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                TimeoutError::Read,
+            ))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl tokio::io::AsyncWrite for CustomStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.io {
+            StreamIo::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            StreamIo::Tls(s) => tokio::io::AsyncWrite::poll_write(Pin::new(s), cx, buf),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.io {
+            StreamIo::Plain(s) => Pin::new(s).poll_flush(cx),
+            StreamIo::Tls(s) => tokio::io::AsyncWrite::poll_flush(Pin::new(s), cx),
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.io {
+            StreamIo::Plain(s) => {
+                s.get_ref().shutdown(Shutdown::Write)?;
+                Poll::Ready(Ok(()))
+            }
+            StreamIo::Tls(s) => tokio::io::AsyncWrite::poll_shutdown(Pin::new(s), cx),
+        }
+    }
+}
+
+impl Connection for CustomStream {
+    fn connected(&self) -> Connected {
+        let connected = Connected::new()
+            .proxy(self.info.forwarded)
+            .extra(self.info.clone());
+        if self.info.http2 {
+            connected.negotiated_h2()
+        } else {
+            connected
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CustomConnector {
+    /// Caps open connections per `host:port`; `None` means unlimited.
+    pub(crate) max_per_host: Option<usize>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) read_timeout: Option<Duration>,
+    /// Offer `h2` as well as `http/1.1` during the TLS handshake.
+    pub(crate) http2: bool,
+    pub(crate) resolver: Arc<dyn Resolve>,
+    pub(crate) proxies: Arc<ProxyConfig>,
+    pub(crate) tls: Arc<TlsConfig>,
+    /// Built from `tls` on the first TLS connection.
+    pub(crate) tls_connector: Arc<OnceCell<tokio_native_tls::TlsConnector>>,
+    pub(crate) hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl Default for CustomConnector {
+    fn default() -> Self {
+        Self {
+            max_per_host: None,
+            connect_timeout: None,
+            read_timeout: None,
+            http2: true,
+            resolver: Arc::new(SystemResolver),
+            proxies: Arc::default(),
+            tls: Arc::default(),
+            tls_connector: Arc::default(),
+            hosts: Arc::default(),
+        }
+    }
+}
+
+impl CustomConnector {
+    fn host_limit(&self, key: String) -> Option<Arc<Semaphore>> {
+        let max = self.max_per_host?;
+        let mut hosts = self.hosts.lock().unwrap();
+        Some(
+            hosts
+                .entry(key)
+                .or_insert_with(|| Arc::new(Semaphore::new(max)))
+                .clone(),
+        )
+    }
+
+    /// Opens the connection for `scheme://host:port`, through a proxy if
+    /// one applies, and runs the TLS handshake for `https`.
+    async fn connect(
+        &self,
+        scheme: &str,
+        host: String,
+        port: u16,
+    ) -> Result<(StreamIo, ConnectionInfo)> {
+        let proxy = self.proxies.proxy_for(scheme, &host);
+        let mut stream = match proxy {
+            Some(proxy) => self.dial(&proxy.host, proxy.port).await?,
+            None => self.dial(&host, port).await?,
+        };
+        let mut info = ConnectionInfo {
+            remote_addr: stream.get_ref().peer_addr()?,
+            local_addr: stream.get_ref().local_addr()?,
+            http2: false,
+            proxied: proxy.is_some(),
+            forwarded: false,
+        };
+        match proxy {
+            Some(proxy) if proxy.kind == ProxyKind::Socks5 => {
+                proxy.socks5(&mut stream, &host, port).await?
+            }
+            // The proxy forwards plain requests itself; hyper then sends
+            // them in absolute form.
+            Some(_) if scheme == "http" => info.forwarded = true,
+            Some(proxy) => proxy.tunnel(&mut stream, &host, port).await?,
+            None => {}
+        }
+        if scheme == "http" {
+            return Ok((StreamIo::Plain(stream), info));
+        }
+
+        let connector = self.tls_connector.get_or_try_init(|| {
+            let alpns: &[&str] = if self.http2 {
+                &["h2", "http/1.1"]
+            } else {
+                &["http/1.1"]
+            };
+            self.tls
+                .connector(alpns)
+                .map(tokio_native_tls::TlsConnector::from)
+        })?;
+        let stream = connector.connect(&host, stream.compat()).await?;
+        info.http2 = stream.get_ref().negotiated_alpn()?.as_deref() == Some(b"h2");
+        Ok((StreamIo::Tls(stream), info))
+    }
+
+    async fn dial(&self, host: &str, port: u16) -> Result<Async<TcpStream>> {
+        let addrs = self.resolver.resolve(host, port).await?;
+        if addrs.is_empty() {
+            bail!("cannot resolve address");
+        }
+        Ok(connect_any(addrs).await?)
+    }
+}
+
+impl hyper::service::Service<Uri> for CustomConnector {
+    type Response = CustomStream;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = self.clone();
+        Box::pin(async move {
+            let host = uri.host().context("cannot parse host")?;
+            let host = host.trim_matches(|c| c == '[' || c == ']').to_string();
+            let (scheme, port) = match uri.scheme_str() {
+                Some("http") => ("http", uri.port_u16().unwrap_or(80)),
+                Some("https") => ("https", uri.port_u16().unwrap_or(443)),
+                scheme => return Err(format!("unsupported scheme: {:?}", scheme).into()),
+            };
+
+            // Waits here while the host is at its limit; hyper hands the
+            // request any pooled connection that frees up in the meantime.
+            let permit = match connector.host_limit(format!("{host}:{port}")) {
+                Some(limit) => Some(limit.acquire_owned().await?),
+                None => None,
+            };
+
+            let connect = connector.connect(scheme, host, port);
+            let (io, info) = match connector.connect_timeout {
+                Some(duration) => timeout(duration, connect)
+                    .await
+                    .map_err(|_| TimeoutError::Connect)??,
+                None => connect.await?,
+            };
+            Ok(CustomStream {
+                io,
+                info,
+                _permit: permit,
+                read_timeout: connector.read_timeout,
+                read_deadline: None,
+            })
+        })
+    }
+}
@@ -0,0 +1,347 @@
+use std::fmt::Write as _;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, Result};
+use http::header::{self, HeaderMap, HeaderValue};
+use http::Uri;
+
+/// A cookie store following the storage and retrieval rules of RFC 6265:
+/// cookies are matched by domain and path, dropped once they expire and
+/// `Secure` ones are only sent over `https`.
+///
+/// The public suffix list is not consulted, so a server can set a cookie
+/// for a whole top-level domain. Point the jar only at sites you trust.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    inner: Mutex<Jar>,
+}
+
+#[derive(Debug, Default)]
+struct Jar {
+    cookies: Vec<Cookie>,
+    /// Orders cookies by creation, for the `Cookie` header.
+    next_seq: u64,
+    /// Whether anything changed since the jar was last saved.
+    changed: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    /// Set without a `Domain` attribute, so only for exactly `domain`.
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    /// `None` for session cookies.
+    expires: Option<SystemTime>,
+    seq: u64,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, secure: bool, host: &str, path: &str) -> bool {
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, &self.domain)
+        };
+        domain_ok && path_match(path, &self.path) && (secure || !self.secure)
+    }
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the cookies from every `Set-Cookie` header in `headers`,
+    /// received in answer to a request for `uri`.
+    pub fn store_response(&self, uri: &Uri, headers: &HeaderMap) {
+        for value in headers.get_all(header::SET_COOKIE) {
+            if let Ok(value) = value.to_str() {
+                self.store(uri, value);
+            }
+        }
+    }
+
+    /// Stores one `Set-Cookie` value received from `uri`. Malformed
+    /// cookies, and cookies for domains `uri` cannot speak for, are
+    /// ignored as the RFC requires.
+    pub fn store(&self, uri: &Uri, set_cookie: &str) {
+        let Some(host) = uri.host() else { return };
+        let Some(mut cookie) = parse(set_cookie, &host.to_ascii_lowercase(), uri.path()) else {
+            return;
+        };
+        let now = SystemTime::now();
+        let mut jar = self.inner.lock().unwrap();
+        let existing = jar.cookies.iter().position(|old| {
+            old.name == cookie.name && old.domain == cookie.domain && old.path == cookie.path
+        });
+        if let Some(index) = existing {
+            let old = jar.cookies.remove(index);
+            cookie.seq = old.seq;
+            jar.changed = true;
+        } else {
+            cookie.seq = jar.next_seq;
+            jar.next_seq += 1;
+        }
+        // An expiry in the past is how servers delete cookies.
+        if !cookie.is_expired(now) {
+            jar.cookies.push(cookie);
+            jar.changed = true;
+        }
+    }
+
+    /// The name and value of each cookie to send with a request for `uri`,
+    /// in `Cookie` header order: longer paths first, then oldest first.
+    pub fn cookies(&self, uri: &Uri) -> Vec<(String, String)> {
+        let Some(host) = uri.host() else {
+            return Vec::new();
+        };
+        let host = host.to_ascii_lowercase();
+        let secure = uri.scheme_str() == Some("https");
+        let now = SystemTime::now();
+        let mut jar = self.inner.lock().unwrap();
+        jar.cookies.retain(|cookie| !cookie.is_expired(now));
+        let mut matching: Vec<_> = jar
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(secure, &host, uri.path()))
+            .collect();
+        matching.sort_by_key(|cookie| (std::cmp::Reverse(cookie.path.len()), cookie.seq));
+        matching
+            .into_iter()
+            .map(|cookie| (cookie.name.clone(), cookie.value.clone()))
+            .collect()
+    }
+
+    /// The `Cookie` header for a request to `uri`, if any cookie applies.
+    pub fn header(&self, uri: &Uri) -> Option<HeaderValue> {
+        let cookies = self.cookies(uri);
+        if cookies.is_empty() {
+            return None;
+        }
+        let mut value = String::new();
+        for (name, cookie) in cookies {
+            if !value.is_empty() {
+                value.push_str("; ");
+            }
+            let _ = write!(value, "{name}={cookie}");
+        }
+        HeaderValue::from_str(&value).ok()
+    }
+
+    pub fn clear(&self) {
+        let mut jar = self.inner.lock().unwrap();
+        jar.changed |= !jar.cookies.is_empty();
+        jar.cookies.clear();
+    }
+
+    /// Reads cookies saved by [`save`](Self::save) (or in curl's
+    /// `cookies.txt` format) into the jar. Expired entries are skipped.
+    pub async fn load(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = smol::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("failed to read cookies from {}", path.display()))?;
+        let now = SystemTime::now();
+        let mut jar = self.inner.lock().unwrap();
+        for (number, line) in text.lines().enumerate() {
+            let cookie = parse_line(line)
+                .with_context(|| format!("{}:{}: invalid cookie", path.display(), number + 1))?;
+            let Some(mut cookie) = cookie else { continue };
+            if cookie.is_expired(now) {
+                continue;
+            }
+            cookie.seq = jar.next_seq;
+            jar.next_seq += 1;
+            jar.cookies.retain(|old| {
+                !(old.name == cookie.name && old.domain == cookie.domain && old.path == cookie.path)
+            });
+            jar.cookies.push(cookie);
+        }
+        Ok(())
+    }
+
+    /// Writes every unexpired cookie, session cookies included, to `path`
+    /// in the Netscape `cookies.txt` format that curl also reads. The file
+    /// is replaced atomically.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = {
+            let now = SystemTime::now();
+            let mut jar = self.inner.lock().unwrap();
+            jar.changed = false;
+            let mut text = String::from("# Netscape HTTP Cookie File\n");
+            for cookie in jar.cookies.iter().filter(|cookie| !cookie.is_expired(now)) {
+                let expires = cookie
+                    .expires
+                    .and_then(|expires| expires.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |since| since.as_secs().max(1));
+                let _ = writeln!(
+                    text,
+                    "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    if cookie.http_only { "#HttpOnly_" } else { "" },
+                    if cookie.host_only { "" } else { "." },
+                    cookie.domain,
+                    if cookie.host_only { "FALSE" } else { "TRUE" },
+                    cookie.path,
+                    if cookie.secure { "TRUE" } else { "FALSE" },
+                    expires,
+                    cookie.name,
+                    cookie.value,
+                );
+            }
+            text
+        };
+        let temp = path.with_extension("tmp");
+        smol::fs::write(&temp, text)
+            .await
+            .with_context(|| format!("failed to write cookies to {}", temp.display()))?;
+        smol::fs::rename(&temp, path)
+            .await
+            .with_context(|| format!("failed to write cookies to {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Whether cookies were added, changed or removed since the last
+    /// [`save`](Self::save).
+    pub fn is_changed(&self) -> bool {
+        self.inner.lock().unwrap().changed
+    }
+}
+
+/// Parses a `Set-Cookie` value (RFC 6265, section 5.2) received from
+/// `host` for a request to `request_path`.
+fn parse(set_cookie: &str, host: &str, request_path: &str) -> Option<Cookie> {
+    let mut parts = set_cookie.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain: host.to_string(),
+        host_only: true,
+        path: default_path(request_path),
+        secure: false,
+        http_only: false,
+        expires: None,
+        seq: 0,
+    };
+    let mut expires = None;
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "expires" => expires = parse_date(value).or(expires),
+            "max-age" => {
+                if let Ok(seconds) = value.parse::<i64>() {
+                    // Zero or less expires the cookie at once. Huge values
+                    // are clamped so the addition cannot overflow.
+                    max_age = Some(match u64::try_from(seconds) {
+                        Ok(seconds) if seconds > 0 => {
+                            SystemTime::now() + Duration::from_secs(seconds.min(u32::MAX.into()))
+                        }
+                        _ => UNIX_EPOCH,
+                    });
+                }
+            }
+            "domain" => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    if !domain_match(host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "path" => cookie.path = default_path(request_path),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            _ => {}
+        }
+    }
+    // Max-Age wins over Expires, whatever their order.
+    cookie.expires = max_age.or(expires);
+    Some(cookie)
+}
+
+/// Accepts the preferred HTTP date format and the older ones, including
+/// the dashed `Wed, 21-Oct-2015 07:28:00 GMT` still common in cookies.
+fn parse_date(value: &str) -> Option<SystemTime> {
+    httpdate::parse_http_date(value)
+        .or_else(|_| httpdate::parse_http_date(&value.replace('-', " ")))
+        .ok()
+}
+
+/// One line of a `cookies.txt` file; `None` for comments and blank lines.
+fn parse_line(line: &str) -> Result<Option<Cookie>> {
+    let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+        Some(rest) => (rest, true),
+        None => (line, false),
+    };
+    if line.trim().is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let fields: Vec<_> = line.split('\t').collect();
+    let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+        anyhow::bail!("expected 7 tab separated fields");
+    };
+    let expires: u64 = expires.parse().context("invalid expiry")?;
+    Ok(Some(Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain: domain.trim_start_matches('.').to_ascii_lowercase(),
+        host_only: subdomains != "TRUE",
+        path: path.to_string(),
+        secure: secure == "TRUE",
+        http_only,
+        expires: (expires != 0).then(|| UNIX_EPOCH + Duration::from_secs(expires)),
+        seq: 0,
+    }))
+}
+
+/// RFC 6265, section 5.1.3.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host
+            .strip_suffix(domain)
+            .is_some_and(|rest| rest.ends_with('.'))
+            && host
+                .trim_matches(|c| c == '[' || c == ']')
+                .parse::<IpAddr>()
+                .is_err())
+}
+
+/// RFC 6265, section 5.1.4.
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// The directory of the request path, as RFC 6265 section 5.1.4 defines
+/// it.
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(slash) => request_path[..slash].to_string(),
+    }
+}
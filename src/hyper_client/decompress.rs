@@ -0,0 +1,50 @@
+use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+use http::header::{self, HeaderValue};
+use hyper::body::HttpBody;
+use hyper::{Body, Response};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use super::BodyStream;
+
+/// What a [`Client`](super::Client) that decompresses asks servers for.
+pub(crate) const ACCEPT_ENCODING: HeaderValue = HeaderValue::from_static("gzip, deflate, br");
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+/// Decodes `response`'s body if it is in one of the encodings in
+/// [`ACCEPT_ENCODING`], dropping the headers that describe the encoded
+/// form. Anything else is passed through untouched.
+pub(crate) fn decode(response: Response<Body>) -> Response<Body> {
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let encoding = match encoding.as_deref() {
+        Some("gzip" | "x-gzip") => Encoding::Gzip,
+        Some("deflate") => Encoding::Deflate,
+        Some("br") => Encoding::Brotli,
+        _ => return response,
+    };
+    // HEAD responses, 204s and 304s carry the header but no body.
+    if response.body().is_end_stream() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let compressed = StreamReader::new(BodyStream::new(body));
+    // `deflate` in HTTP means the zlib format (RFC 9110, section 8.4.1.2).
+    let body = match encoding {
+        Encoding::Gzip => Body::wrap_stream(ReaderStream::new(GzipDecoder::new(compressed))),
+        Encoding::Deflate => Body::wrap_stream(ReaderStream::new(ZlibDecoder::new(compressed))),
+        Encoding::Brotli => Body::wrap_stream(ReaderStream::new(BrotliDecoder::new(compressed))),
+    };
+    Response::from_parts(parts, body)
+}
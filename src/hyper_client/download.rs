@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context as _, Result};
+use futures::StreamExt;
+use http::{header, Request, Response, StatusCode, Uri};
+use hyper::Body;
+use smol::fs::{File, OpenOptions};
+use smol::io::AsyncWriteExt;
+
+use super::body::{into_anyhow, BodyStream, BodyTooLarge};
+use super::Client;
+
+/// How far a [`Download`] has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes in the file so far, including any resumed from an earlier run.
+    pub downloaded: u64,
+    /// Size of the complete file, if the server said.
+    pub total: Option<u64>,
+}
+
+/// Streams a response body into a file; built by [`Client::download`].
+pub struct Download<'a> {
+    client: &'a Client,
+    uri: Uri,
+    path: PathBuf,
+    resume: bool,
+    progress: Option<Box<dyn FnMut(Progress) + Send + 'a>>,
+}
+
+impl<'a> Download<'a> {
+    pub(crate) fn new(client: &'a Client, uri: Uri, path: PathBuf) -> Self {
+        Self {
+            client,
+            uri,
+            path,
+            resume: false,
+            progress: None,
+        }
+    }
+
+    /// Continue a partial file with a `Range` request instead of starting
+    /// over. Servers that ignore the range get the file rewritten from the
+    /// start.
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Called after every chunk written, and once before the first.
+    pub fn on_progress(mut self, progress: impl FnMut(Progress) + Send + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Runs the download and returns the final size of the file.
+    pub async fn send(mut self) -> Result<u64> {
+        let existing = if self.resume {
+            smol::fs::metadata(&self.path)
+                .await
+                .map(|meta| meta.len())
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        // Ranges must refer to the bytes stored on disk, so ask for them as
+        // they are; this also keeps a decompressing client from decoding.
+        let mut request =
+            Request::get(self.uri.clone()).header(header::ACCEPT_ENCODING, "identity");
+        if existing > 0 {
+            request = request.header(header::RANGE, format!("bytes={existing}-"));
+        }
+        let response = self.client.request(request.body(Body::empty())?).await?;
+
+        let (mut file, mut downloaded, total) = match response.status() {
+            StatusCode::PARTIAL_CONTENT if existing > 0 => {
+                let (start, total) = content_range(&response)?;
+                ensure!(
+                    start == existing,
+                    "server resumed at byte {start}, expected {existing}"
+                );
+                let file = OpenOptions::new().append(true).open(&self.path).await?;
+                (file, existing, total)
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE if existing > 0 => {
+                // Nothing left to fetch if the file is already complete.
+                if content_range_total(&response) == Some(existing) {
+                    self.report(existing, Some(existing));
+                    return Ok(existing);
+                }
+                bail!("server rejected resuming {} at byte {existing}", self.uri);
+            }
+            status if status.is_success() => {
+                let total = response_length(&response);
+                (File::create(&self.path).await?, 0, total)
+            }
+            status => bail!("download of {} failed: {status}", self.uri),
+        };
+        if let (Some(total), Some(limit)) = (total, self.client.max_body_size) {
+            ensure!(total <= limit, BodyTooLarge { limit });
+        }
+
+        self.report(downloaded, total);
+        let mut body = BodyStream::new(response.into_body());
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(into_anyhow)?;
+            if let Some(limit) = self.client.max_body_size {
+                ensure!(
+                    downloaded + chunk.len() as u64 <= limit,
+                    BodyTooLarge { limit }
+                );
+            }
+            file.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            self.report(downloaded, total);
+        }
+        file.flush().await?;
+        file.sync_all().await?;
+
+        if let Some(total) = total {
+            ensure!(
+                downloaded == total,
+                "download of {} ended at byte {downloaded} of {total}",
+                self.uri
+            );
+        }
+        Ok(downloaded)
+    }
+
+    fn report(&mut self, downloaded: u64, total: Option<u64>) {
+        if let Some(progress) = &mut self.progress {
+            progress(Progress { downloaded, total });
+        }
+    }
+}
+
+fn response_length<B>(response: &Response<B>) -> Option<u64> {
+    response
+        .headers()
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Parses `Content-Range: bytes start-end/total` into `(start, total)`.
+fn content_range<B>(response: &Response<B>) -> Result<(u64, Option<u64>)> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .context("partial response without Content-Range")?
+        .to_str()?;
+    let parse = || {
+        let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+        let start = range.split_once('-')?.0.parse().ok()?;
+        Some((start, total.parse().ok()))
+    };
+    parse().with_context(|| format!("invalid Content-Range: {value:?}"))
+}
+
+/// The total from `Content-Range: bytes */total`, sent with a 416.
+fn content_range_total<B>(response: &Response<B>) -> Option<u64> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    value.strip_prefix("bytes */")?.parse().ok()
+}
@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Which of the [`Client`](super::Client) timeouts fired.
+///
+/// Requests fail with an `anyhow::Error` that downcasts to this type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutError {
+    /// Resolving, connecting and the TLS handshake took too long.
+    Connect,
+    /// The server sent nothing for longer than the read timeout.
+    Read,
+    /// The request, including retries, did not get response headers in time.
+    Total,
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeoutError::Connect => "connect timed out",
+            TimeoutError::Read => "read timed out",
+            TimeoutError::Total => "request timed out",
+        })
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// Finds a [`TimeoutError`] in `err`'s source chain. Looks inside
+/// `io::Error`s too, since their `source` skips the wrapped error.
+pub(crate) fn find_timeout(err: &(dyn Error + 'static)) -> Option<TimeoutError> {
+    let mut next = Some(err);
+    while let Some(err) = next {
+        if let Some(timeout) = err.downcast_ref::<TimeoutError>() {
+            return Some(*timeout);
+        }
+        if let Some(inner) = err.downcast_ref::<io::Error>().and_then(io::Error::get_ref) {
+            if let Some(timeout) = find_timeout(inner) {
+                return Some(timeout);
+            }
+        }
+        next = err.source();
+    }
+    None
+}
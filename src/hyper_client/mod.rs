@@ -0,0 +1,101 @@
+//! An HTTP client that runs `hyper` on the abstracted runtime in
+//! [`crate::runtime`], with `smol` providing the sockets.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use anyhow::Result;
+use hyper::{Body, Request, Response};
+use once_cell::sync::Lazy;
+
+use crate::runtime::{spawn_task_function, FutureType};
+use crate::spawn_task;
+
+mod batch;
+mod body;
+mod client;
+mod connector;
+mod cookie;
+mod decompress;
+mod download;
+mod error;
+mod proxy;
+mod redirect;
+mod resolve;
+mod retry;
+mod session;
+mod tls;
+
+pub use batch::{BatchFetch, BatchResult, BatchStream};
+pub use body::{BodyStream, BodyTooLarge};
+pub use client::{Client, ClientBuilder};
+pub use connector::ConnectionInfo;
+pub use cookie::CookieJar;
+pub use download::{Download, Progress};
+pub use error::TimeoutError;
+pub use native_tls::{Certificate, Identity, Protocol};
+pub use proxy::{Proxy, ProxyConfig};
+pub use redirect::{RedirectError, RedirectPolicy};
+pub use resolve::{Resolve, Resolving, StaticResolver, SystemResolver};
+pub use retry::RetryPolicy;
+pub use session::Session;
+pub use tls::TlsConfig;
+
+/// Sends `req` with a process-wide default [`Client`], so repeated calls
+/// share its connection pool. It gives up on connecting after 10 seconds
+/// and on a silent server after 30. Like [`Client::builder`], it returns
+/// redirects as they are and connects directly; build a [`Client`] with a
+/// [`RedirectPolicy`] or [`ProxyConfig::from_env`] for either.
+pub async fn fetch(req: Request<Body>) -> Result<Response<Body>> {
+    default_client().request(req).await
+}
+
+fn default_client() -> &'static Client {
+    static DEFAULT: Lazy<Client> = Lazy::new(|| {
+        Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .read_timeout(Duration::from_secs(30))
+            .build()
+    });
+    &DEFAULT
+}
+
+/// Spawns hyper's background work (connection drivers, pool upkeep) onto
+/// the runtime's low priority queue.
+#[derive(Clone, Copy)]
+pub struct CustomExecutor;
+
+impl<F: Future + Send + 'static> hyper::rt::Executor<F> for CustomExecutor {
+    fn execute(&self, future: F) {
+        spawn_task!(async {
+            WithTimer(future).await;
+        })
+        .detach();
+    }
+}
+
+/// hyper's pool creates its idle reaper with `tokio::time::interval`, which
+/// needs a Tokio timer in scope. This runtime provides one and does nothing
+/// else; hyper futures enter it on every poll.
+static TOKIO_TIMER: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("hyper-timer")
+        .enable_time()
+        .build()
+        .expect("failed to start the hyper timer")
+});
+
+pub(crate) struct WithTimer<F>(pub(crate) F);
+
+impl<F: Future> Future for WithTimer<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let _guard = TOKIO_TIMER.enter();
+        // SAFETY: the inner future is never moved out of `self`.
+        unsafe { self.map_unchecked_mut(|this| &mut this.0) }.poll(cx)
+    }
+}
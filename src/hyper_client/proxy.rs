@@ -0,0 +1,294 @@
+use std::env;
+use std::net::{IpAddr, TcpStream};
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context as _, Error, Result};
+use base64::Engine as _;
+use http::header::HeaderValue;
+use smol::{prelude::*, Async};
+
+use crate::server::percent_decode;
+
+/// A proxy server, written as `http://[user:pass@]host[:port]` or
+/// `socks5://[user:pass@]host[:port]`. The user and password are
+/// percent-decoded, so `p%40ss` stands for `p@ss`.
+///
+/// HTTP proxies forward plain `http://` requests and tunnel everything else
+/// with `CONNECT`. SOCKS5 proxies tunnel everything and resolve host names
+/// on the proxy side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proxy {
+    pub(crate) kind: ProxyKind,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    credentials: Option<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProxyKind {
+    Http,
+    Socks5,
+}
+
+impl FromStr for Proxy {
+    type Err = Error;
+
+    fn from_str(url: &str) -> Result<Self> {
+        let url = url.trim();
+        // Bare `host:port`, as often found in the environment, means HTTP.
+        let (scheme, rest) = url.split_once("://").unwrap_or(("http", url));
+        let (kind, default_port) = match scheme.to_ascii_lowercase().as_str() {
+            "http" => (ProxyKind::Http, 80),
+            "socks5" | "socks5h" => (ProxyKind::Socks5, 1080),
+            scheme => bail!("unsupported proxy scheme: {scheme:?}"),
+        };
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (credentials, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => {
+                let (user, pass) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+                let decode =
+                    |part| percent_decode(part).context("proxy credentials are not valid UTF-8");
+                (Some((decode(user)?, decode(pass)?)), host_port)
+            }
+            None => (None, authority),
+        };
+        let authority: http::uri::Authority = host_port
+            .parse()
+            .with_context(|| format!("invalid proxy address: {host_port:?}"))?;
+        let host = authority.host().trim_matches(|c| c == '[' || c == ']');
+        ensure!(!host.is_empty(), "proxy address has no host");
+        Ok(Self {
+            kind,
+            host: host.to_string(),
+            port: authority.port_u16().unwrap_or(default_port),
+            credentials,
+        })
+    }
+}
+
+impl Proxy {
+    /// The `Proxy-Authorization` value for an HTTP proxy with credentials.
+    pub(crate) fn basic_auth(&self) -> Option<HeaderValue> {
+        let (user, pass) = self.credentials.as_ref()?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(format!("{user}:{pass}"));
+        let mut value = HeaderValue::from_str(&format!("Basic {encoded}")).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+
+    /// Asks the HTTP proxy on `stream` to open a tunnel to `host:port`.
+    pub(crate) async fn tunnel(
+        &self,
+        stream: &mut Async<TcpStream>,
+        host: &str,
+        port: u16,
+    ) -> Result<()> {
+        let target = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{ip}]:{port}"),
+            _ => format!("{host}:{port}"),
+        };
+        let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+        if let Some(auth) = self.basic_auth() {
+            request.push_str("Proxy-Authorization: ");
+            request.push_str(auth.to_str()?);
+            request.push_str("\r\n");
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // Read byte by byte so nothing past the proxy's reply is consumed.
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            ensure!(head.len() < 8192, "proxy response head too large");
+            let mut byte = [0];
+            stream
+                .read_exact(&mut byte)
+                .await
+                .context("proxy closed the connection during CONNECT")?;
+            head.push(byte[0]);
+        }
+        let status_line = String::from_utf8_lossy(&head);
+        let status_line = status_line.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => bail!("proxy refused CONNECT to {target}: {status_line}"),
+        }
+    }
+
+    /// Runs the SOCKS5 handshake (RFC 1928, with RFC 1929 username and
+    /// password auth) to connect the proxy on `stream` to `host:port`.
+    pub(crate) async fn socks5(
+        &self,
+        stream: &mut Async<TcpStream>,
+        host: &str,
+        port: u16,
+    ) -> Result<()> {
+        const NO_AUTH: u8 = 0x00;
+        const USER_PASS: u8 = 0x02;
+
+        let method = if self.credentials.is_some() {
+            USER_PASS
+        } else {
+            NO_AUTH
+        };
+        stream.write_all(&[5, 1, method]).await?;
+        let mut reply = [0; 2];
+        stream.read_exact(&mut reply).await?;
+        ensure!(reply[0] == 5, "not a SOCKS5 proxy");
+        ensure!(
+            reply[1] == method,
+            "SOCKS5 proxy rejected our authentication method"
+        );
+
+        if let Some((user, pass)) = &self.credentials {
+            ensure!(
+                user.len() <= 255 && pass.len() <= 255,
+                "SOCKS5 credentials too long"
+            );
+            let mut auth = vec![1, user.len() as u8];
+            auth.extend_from_slice(user.as_bytes());
+            auth.push(pass.len() as u8);
+            auth.extend_from_slice(pass.as_bytes());
+            stream.write_all(&auth).await?;
+            stream.read_exact(&mut reply).await?;
+            ensure!(reply[1] == 0, "SOCKS5 proxy rejected the credentials");
+        }
+
+        let mut request = vec![5, 1, 0];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(1);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                ensure!(host.len() <= 255, "host name too long for SOCKS5");
+                request.push(3);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut head = [0; 4];
+        stream.read_exact(&mut head).await?;
+        ensure!(head[0] == 5, "not a SOCKS5 reply");
+        ensure!(
+            head[1] == 0,
+            "SOCKS5 proxy could not connect to {host}:{port} (reply {})",
+            head[1]
+        );
+        // Skip the bound address the proxy reports.
+        let address_len = match head[3] {
+            1 => 4,
+            4 => 16,
+            3 => {
+                let mut len = [0];
+                stream.read_exact(&mut len).await?;
+                len[0] as usize
+            }
+            atyp => bail!("SOCKS5 proxy sent unknown address type {atyp}"),
+        };
+        let mut bound = vec![0; address_len + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(())
+    }
+}
+
+/// Which proxy, if any, each request goes through.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyConfig {
+    http: Option<Proxy>,
+    https: Option<Proxy>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Connect to every host directly.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Reads `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`, in upper or lower
+    /// case. Unset or empty variables mean no proxy; unparsable ones are
+    /// logged and ignored.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// [`from_env`](Self::from_env), with the variables looked up by `var`
+    /// instead.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| {
+            var(name)
+                .or_else(|| var(&name.to_ascii_lowercase()))
+                .filter(|value| !value.trim().is_empty())
+        };
+        let proxy = |name: &str| {
+            let value = var(name)?;
+            value
+                .parse()
+                .map_err(|err| tracing::warn!(variable = name, error = %err, "ignoring proxy"))
+                .ok()
+        };
+        Self {
+            http: proxy("HTTP_PROXY"),
+            https: proxy("HTTPS_PROXY"),
+            no_proxy: Vec::new(),
+        }
+        .no_proxy(&var("NO_PROXY").unwrap_or_default())
+    }
+
+    /// Send `http://` requests through `proxy`.
+    pub fn http(mut self, proxy: Proxy) -> Self {
+        self.http = Some(proxy);
+        self
+    }
+
+    /// Send `https://` requests through `proxy`.
+    pub fn https(mut self, proxy: Proxy) -> Self {
+        self.https = Some(proxy);
+        self
+    }
+
+    /// Send all requests through `proxy`.
+    pub fn all(self, proxy: Proxy) -> Self {
+        self.http(proxy.clone()).https(proxy)
+    }
+
+    /// Hosts to reach directly, as a comma separated `NO_PROXY` style list.
+    /// `example.com` and `.example.com` both match the domain and its
+    /// subdomains; `*` matches everything.
+    pub fn no_proxy(mut self, hosts: &str) -> Self {
+        self.no_proxy.extend(
+            hosts
+                .split(',')
+                .map(|host| host.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|host| !host.is_empty()),
+        );
+        self
+    }
+
+    pub(crate) fn proxy_for(&self, scheme: &str, host: &str) -> Option<&Proxy> {
+        let proxy = match scheme {
+            "http" => self.http.as_ref(),
+            "https" => self.https.as_ref(),
+            _ => None,
+        }?;
+        let host = host
+            .trim_matches(|c| c == '[' || c == ']')
+            .to_ascii_lowercase();
+        let bypass = self.no_proxy.iter().any(|entry| {
+            entry == "*"
+                || host == *entry
+                || host
+                    .strip_suffix(entry.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        });
+        (!bypass).then_some(proxy)
+    }
+}
@@ -0,0 +1,187 @@
+use std::error::Error;
+use std::fmt;
+
+use anyhow::{bail, Context as _, Result};
+use http::request::Parts;
+use http::{header, Method, Response, StatusCode, Uri};
+use hyper::body::Bytes;
+
+/// Whether and how far a [`Client`](super::Client) follows `3xx`
+/// redirects.
+///
+/// `303 See Other`, and `301`/`302` in answer to a `POST`, are followed
+/// with a bodiless `GET`; other redirects repeat the request as it was.
+/// `Authorization` and `Cookie` headers are dropped whenever a redirect
+/// leads to another origin.
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    max_hops: usize,
+    same_origin_only: bool,
+}
+
+impl RedirectPolicy {
+    /// Hand `3xx` responses back to the caller.
+    pub fn none() -> Self {
+        Self::limited(0)
+    }
+
+    /// Follow up to `max_hops` redirects in a row, then fail with
+    /// [`RedirectError::TooMany`].
+    pub fn limited(max_hops: usize) -> Self {
+        Self {
+            max_hops,
+            same_origin_only: false,
+        }
+    }
+
+    /// Fail with [`RedirectError::CrossOrigin`] instead of following a
+    /// redirect to another scheme, host or port.
+    pub fn same_origin_only(mut self, same_origin_only: bool) -> Self {
+        self.same_origin_only = same_origin_only;
+        self
+    }
+
+    pub(crate) fn follows(&self) -> bool {
+        self.max_hops > 0
+    }
+
+    /// Checks that one more hop, from `origin` to `location`, is allowed.
+    pub(crate) fn check(
+        &self,
+        hops: usize,
+        origin: &Uri,
+        location: &Uri,
+    ) -> Result<(), RedirectError> {
+        if hops == self.max_hops {
+            return Err(RedirectError::TooMany(self.max_hops));
+        }
+        if self.same_origin_only && !same_origin(origin, location) {
+            return Err(RedirectError::CrossOrigin(location.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Why a [`Client`](super::Client) stopped following redirects.
+///
+/// Requests fail with an `anyhow::Error` that downcasts to this type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectError {
+    /// The response was still a redirect after this many hops.
+    TooMany(usize),
+    /// A redirect pointed at another origin, which the policy forbids.
+    CrossOrigin(Uri),
+}
+
+impl fmt::Display for RedirectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedirectError::TooMany(max) => write!(f, "more than {max} redirects"),
+            RedirectError::CrossOrigin(location) => {
+                write!(f, "redirect to another origin: {location}")
+            }
+        }
+    }
+}
+
+impl Error for RedirectError {}
+
+/// Where `response`, an answer to a request for `base`, redirects to, if
+/// it is a redirect.
+pub(crate) fn location<B>(base: &Uri, response: &Response<B>) -> Result<Option<Uri>> {
+    if !matches!(
+        response.status(),
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    ) {
+        return Ok(None);
+    }
+    // Without a Location there is nowhere to go; the caller gets the 3xx.
+    let Some(location) = response.headers().get(header::LOCATION) else {
+        return Ok(None);
+    };
+    let location = location.to_str().context("invalid Location header")?;
+    let uri = resolve(base, location)?;
+    if !matches!(uri.scheme_str(), Some("http" | "https")) {
+        bail!("redirect to unsupported URI: {uri}");
+    }
+    Ok(Some(uri))
+}
+
+/// Turns the request in `parts` and `body` into the one that follows a
+/// `status` redirect to `location`.
+pub(crate) fn redirect(parts: &mut Parts, body: &mut Bytes, status: StatusCode, location: Uri) {
+    let to_get = match status {
+        StatusCode::SEE_OTHER => parts.method != Method::HEAD,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => parts.method == Method::POST,
+        _ => false,
+    };
+    if to_get {
+        parts.method = Method::GET;
+        *body = Bytes::new();
+        for name in [
+            header::CONTENT_TYPE,
+            header::CONTENT_LENGTH,
+            header::CONTENT_ENCODING,
+            header::TRANSFER_ENCODING,
+        ] {
+            parts.headers.remove(name);
+        }
+    }
+    if !same_origin(&parts.uri, &location) {
+        parts.headers.remove(header::AUTHORIZATION);
+        parts.headers.remove(header::COOKIE);
+    }
+    parts.headers.remove(header::HOST);
+    parts.uri = location;
+}
+
+fn same_origin(a: &Uri, b: &Uri) -> bool {
+    a.scheme() == b.scheme()
+        && a.host().map(str::to_ascii_lowercase) == b.host().map(str::to_ascii_lowercase)
+        && port(a) == port(b)
+}
+
+fn port(uri: &Uri) -> Option<u16> {
+    uri.port_u16().or(match uri.scheme_str() {
+        Some("http") => Some(80),
+        Some("https") => Some(443),
+        _ => None,
+    })
+}
+
+/// Resolves a `Location` value, which may be relative, against `base`.
+fn resolve(base: &Uri, location: &str) -> Result<Uri> {
+    // Fragments stay on the client side.
+    let location = location.trim().split('#').next().unwrap_or_default();
+    if let Ok(uri) = location.parse::<Uri>() {
+        if uri.scheme().is_some() {
+            return Ok(uri);
+        }
+    }
+    let scheme = base.scheme_str().unwrap_or("http");
+    let authority = base.authority().context("request URI has no host")?;
+    let target = if let Some(rest) = location.strip_prefix("//") {
+        format!("{scheme}://{rest}")
+    } else if location.starts_with('/') {
+        format!("{scheme}://{authority}{location}")
+    } else if location.starts_with('?') {
+        format!("{scheme}://{authority}{}{location}", base.path())
+    } else {
+        let path = base.path();
+        let dir = &path[..path.rfind('/').map_or(0, |slash| slash + 1)];
+        format!("{scheme}://{authority}{dir}{location}")
+    };
+    target
+        .parse()
+        .with_context(|| format!("invalid redirect location: {location:?}"))
+}
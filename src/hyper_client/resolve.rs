@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use smol::Async;
+
+use crate::runtime::sleep;
+
+/// How long to wait on one connection attempt before racing the next
+/// address, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+pub type Resolving = Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send>>;
+
+/// Turns a host name into the addresses a [`Client`](super::Client)
+/// connects to.
+pub trait Resolve: Send + Sync + 'static {
+    /// All addresses for `host`, most preferred first.
+    fn resolve(&self, host: &str, port: u16) -> Resolving;
+}
+
+/// The operating system's resolver, run on a blocking thread pool.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> Resolving {
+        let host = host.to_string();
+        Box::pin(smol::unblock(move || {
+            Ok((host.as_str(), port).to_socket_addrs()?.collect())
+        }))
+    }
+}
+
+/// Answers for a fixed set of hosts and asks another resolver about the
+/// rest. Handy for pointing real host names at a local test server.
+#[derive(Clone)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<SocketAddr>>,
+    fallback: Arc<dyn Resolve>,
+}
+
+impl StaticResolver {
+    /// No overrides yet, falling back to the [`SystemResolver`].
+    pub fn new() -> Self {
+        Self {
+            hosts: HashMap::new(),
+            fallback: Arc::new(SystemResolver),
+        }
+    }
+
+    /// Resolves `host` to exactly `addrs`, ports included; the port in the
+    /// request is ignored.
+    pub fn with(
+        mut self,
+        host: impl Into<String>,
+        addrs: impl IntoIterator<Item = SocketAddr>,
+    ) -> Self {
+        self.hosts
+            .entry(host.into().to_ascii_lowercase())
+            .or_default()
+            .extend(addrs);
+        self
+    }
+
+    pub fn fallback(mut self, resolver: impl Resolve) -> Self {
+        self.fallback = Arc::new(resolver);
+        self
+    }
+}
+
+impl Default for StaticResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolve for StaticResolver {
+    fn resolve(&self, host: &str, port: u16) -> Resolving {
+        match self.hosts.get(&host.to_ascii_lowercase()) {
+            Some(addrs) => {
+                let addrs = addrs.clone();
+                Box::pin(async move { Ok(addrs) })
+            }
+            None => self.fallback.resolve(host, port),
+        }
+    }
+}
+
+/// Connects to whichever of `addrs` answers first, following RFC 8305
+/// ("happy eyeballs"): address families are interleaved, and a new attempt
+/// starts whenever the previous one fails or has been pending for
+/// [`CONNECTION_ATTEMPT_DELAY`]. Losing attempts are dropped.
+pub(crate) async fn connect_any(addrs: Vec<SocketAddr>) -> io::Result<Async<TcpStream>> {
+    let mut queue = interleave(addrs).into_iter().peekable();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error = None;
+    loop {
+        if let Some(addr) = queue.next() {
+            attempts.push(attempt(addr));
+        }
+        if attempts.is_empty() {
+            return Err(last_error.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to")
+            }));
+        }
+
+        let mut delay = sleep(CONNECTION_ATTEMPT_DELAY);
+        let more = queue.peek().is_some();
+        let finished = futures::future::poll_fn(|cx| {
+            if let Poll::Ready(Some(result)) = attempts.poll_next_unpin(cx) {
+                return Poll::Ready(Some(result));
+            }
+            if more && Pin::new(&mut delay).poll(cx).is_ready() {
+                return Poll::Ready(None);
+            }
+            Poll::Pending
+        })
+        .await;
+        match finished {
+            Some((_, Ok(stream))) => return Ok(stream),
+            Some((addr, Err(err))) => {
+                tracing::debug!(%addr, error = %err, "connection attempt failed");
+                last_error = Some(err);
+            }
+            // The delay passed; start the next attempt alongside this one.
+            None => {}
+        }
+    }
+}
+
+async fn attempt(addr: SocketAddr) -> (SocketAddr, io::Result<Async<TcpStream>>) {
+    (addr, Async::<TcpStream>::connect(addr).await)
+}
+
+/// Alternates address families, starting with the family of the first
+/// (most preferred) address.
+fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_v6 = first.is_ipv6();
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_v6);
+    let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::{Duration, SystemTime};
+
+use http::{header, Method, Response, StatusCode};
+use rand::Rng;
+
+/// When and how often a [`Client`](super::Client) retries a request.
+///
+/// Only idempotent methods are retried, and only after a connect error or a
+/// `5xx` / `429 Too Many Requests` response. Waits grow exponentially from
+/// `base_delay` with random jitter, capped at `max_delay`; a `Retry-After`
+/// header on the response takes precedence, within the same cap.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        Self::new(0)
+    }
+
+    /// Up to `max_retries` retries, backing off from 100ms to at most 10s.
+    pub fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub(crate) fn applies_to(&self, method: &Method) -> bool {
+        self.max_retries > 0 && method.is_idempotent()
+    }
+
+    pub(crate) fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// The wait before retry number `attempt` (starting at 0).
+    pub(crate) fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        // Half fixed, half random, so clients that failed together spread
+        // out without any of them retrying immediately.
+        let half = ceiling / 2;
+        half + half.mul_f64(rand::rng().random::<f64>())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Parses `Retry-After` as either delay-seconds or an HTTP date.
+pub(crate) fn retry_after<B>(response: &Response<B>) -> Option<Duration> {
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
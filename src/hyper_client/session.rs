@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use http::header::{HeaderName, HeaderValue};
+use http::Uri;
+use hyper::{Body, Request, Response};
+
+use super::{Client, CookieJar};
+use crate::sync::Mutex;
+
+/// A run of requests that share cookies and default headers, such as a
+/// logged-in scraping job.
+///
+/// A session sits on top of a [`Client`] and shares its connection pool,
+/// timeouts and policies, so one client can serve many sessions. Cookies
+/// are kept in a [`CookieJar`] and, for [`persistent`](Self::persistent)
+/// sessions, written back to disk whenever a response changes them.
+#[derive(Clone)]
+pub struct Session {
+    client: Client,
+    jar: Arc<CookieJar>,
+    file: Option<Arc<SessionFile>>,
+}
+
+struct SessionFile {
+    path: PathBuf,
+    /// Keeps concurrent requests from writing the file at the same time.
+    lock: Mutex<()>,
+}
+
+impl Session {
+    /// A session with an empty cookie jar.
+    pub fn new(client: &Client) -> Self {
+        let jar = Arc::new(CookieJar::new());
+        let mut client = client.clone();
+        client.cookies = Some(jar.clone());
+        Self {
+            client,
+            jar,
+            file: None,
+        }
+    }
+
+    /// A session whose cookies live in the file at `path`, which is read
+    /// now if it exists.
+    pub async fn persistent(client: &Client, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut session = Self::new(client);
+        if smol::fs::metadata(&path).await.is_ok() {
+            session.jar.load(&path).await?;
+        }
+        session.file = Some(Arc::new(SessionFile {
+            path,
+            lock: Mutex::new(()),
+        }));
+        Ok(session)
+    }
+
+    /// Sends `value` as `name` on every request that does not set `name`
+    /// itself.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        Arc::make_mut(&mut self.client.default_headers).insert(name, value);
+        self
+    }
+
+    pub fn cookies(&self) -> &CookieJar {
+        &self.jar
+    }
+
+    /// The client behind the session, with its cookies and default headers
+    /// attached; use it for [`Client::stream`] or [`Client::download`].
+    /// Cookies it receives are only written to disk on the next
+    /// [`save`](Self::save) or session request.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Sends `req` with the session's cookies and default headers, and
+    /// keeps any cookies the responses set, including those on redirects.
+    pub async fn request(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let response = self.client.request(req).await;
+        // Save even after an error: a redirect may have set cookies first.
+        if self.jar.is_changed() {
+            self.save().await?;
+        }
+        response
+    }
+
+    pub async fn get(&self, uri: Uri) -> Result<Response<Body>> {
+        self.request(Request::get(uri).body(Body::empty())?).await
+    }
+
+    /// Writes the cookies to the session's file, if it has one.
+    pub async fn save(&self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let _guard = file.lock.lock().await;
+        self.jar.save(&file.path).await
+    }
+}
@@ -0,0 +1,62 @@
+use native_tls::{Certificate, Identity, Protocol, TlsConnector};
+
+/// How a [`Client`](super::Client) sets up TLS connections.
+///
+/// By default the system's trust roots verify servers, no client
+/// certificate is offered and the platform's minimum protocol applies.
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    roots: Vec<Certificate>,
+    identity: Option<Identity>,
+    min_protocol: Option<Protocol>,
+    accept_invalid_certs: bool,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts `cert` in addition to the system roots, e.g. an internal CA.
+    pub fn root_certificate(mut self, cert: Certificate) -> Self {
+        self.roots.push(cert);
+        self
+    }
+
+    /// The certificate and key to present when a server asks for one.
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Refuses to negotiate anything older than `protocol`.
+    pub fn min_protocol_version(mut self, protocol: Protocol) -> Self {
+        self.min_protocol = Some(protocol);
+        self
+    }
+
+    /// Accepts any server certificate, valid or not, for any host name.
+    ///
+    /// This removes the protection TLS offers against impersonation. Use it
+    /// for tests against throwaway servers, never in production.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    pub(crate) fn connector(&self, alpns: &[&str]) -> native_tls::Result<TlsConnector> {
+        let mut builder = TlsConnector::builder();
+        builder
+            .request_alpns(alpns)
+            .min_protocol_version(self.min_protocol)
+            .danger_accept_invalid_certs(self.accept_invalid_certs)
+            .danger_accept_invalid_hostnames(self.accept_invalid_certs);
+        for root in &self.roots {
+            builder.add_root_certificate(root.clone());
+        }
+        if let Some(identity) = &self.identity {
+            builder.identity(identity.clone());
+        }
+        builder.build()
+    }
+}
@@ -1,2 +1,8 @@
+pub mod hyper_client;
 pub mod model;
 pub mod runtime;
+pub mod server;
+pub mod sync;
+pub mod test;
+
+pub use rust_concurrency_macros::test;
@@ -1,54 +1,445 @@
+//! One HTTP server, four ways to run it. Every mode serves the same router
+//! with the same connection handling, so comparing them measures only the
+//! concurrency model:
+//!
+//! * `single`: one thread, one connection at a time.
+//! * `pool`: a [`ThreadPool`] of `--workers` threads, one connection each.
+//!   A handler that panics does not take its worker down.
+//! * `tokio`: a multi-threaded Tokio runtime with `--workers` threads.
+//! * `custom-runtime`: this crate's runtime with `--workers` Low workers.
+//!
+//! ```text
+//! cargo run --release -- --mode pool --workers 4 --addr 127.0.0.1:8787 --root public
+//! ```
+//!
+//! On SIGINT or SIGTERM the server stops accepting connections, lets the
+//! open ones finish their current request for up to `--drain-timeout`
+//! seconds, and then exits. A second signal exits at once.
+
 use std::{
-    fs,
-    io::{prelude::*, BufReader},
-    net::{TcpListener, TcpStream},
+    env, fmt,
+    future::Future,
+    io, mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::Arc,
     thread,
     time::Duration,
 };
 
-use threadpool::ThreadPool;
+use futures::io::{AsyncRead, AsyncWrite};
+use futures_lite::future;
+use http::{Request, Uri};
+use rust_concurrency::model::ThreadPool;
+use rust_concurrency::runtime::{self, spawn_task_function, FutureType, Runtime};
+use rust_concurrency::server::{
+    serve_async_until, serve_until, Handler, KeepAlive, Limits, Router, Shutdown, StaticFiles,
+};
+use rust_concurrency::spawn_task;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+const USAGE: &str = "\
+usage: rust-concurrency [--mode single|pool|tokio|custom-runtime] [--workers N]
+                        [--addr HOST:PORT] [--root DIR] [--drain-timeout SECS]
+
+  --mode     concurrency model (default: pool)
+  --workers  threads serving connections (default: 4)
+  --addr     address to listen on (default: 127.0.0.1:8787)
+  --root     directory of static files (default: public)
+  --drain-timeout
+             seconds open connections get to finish after SIGINT or
+             SIGTERM (default: 10)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Single,
+    Pool,
+    Tokio,
+    CustomRuntime,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "single" => Ok(Mode::Single),
+            "pool" => Ok(Mode::Pool),
+            "tokio" => Ok(Mode::Tokio),
+            "custom-runtime" => Ok(Mode::CustomRuntime),
+            _ => Err(format!("unknown mode {s:?}")),
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Single => "single",
+            Mode::Pool => "pool",
+            Mode::Tokio => "tokio",
+            Mode::CustomRuntime => "custom-runtime",
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Args {
+    mode: Mode,
+    workers: usize,
+    addr: SocketAddr,
+    root: PathBuf,
+    drain_timeout: Duration,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args {
+            mode: Mode::Pool,
+            workers: 4,
+            // 监听地址：127.0.0.1:8787
+            addr: SocketAddr::from(([127, 0, 0, 1], 8787)),
+            root: PathBuf::from("public"),
+            drain_timeout: Duration::from_secs(10),
+        };
+        while let Some(flag) = args.next() {
+            if flag == "-h" || flag == "--help" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{flag} needs a value"))?;
+            let invalid = |err: &dyn fmt::Display| format!("invalid {flag} {value:?}: {err}");
+            match flag.as_str() {
+                "--mode" => parsed.mode = value.parse().map_err(|err| invalid(&err))?,
+                "--workers" => {
+                    parsed.workers = value.parse().map_err(|err| invalid(&err))?;
+                    if parsed.workers == 0 {
+                        return Err(invalid(&"must be at least 1"));
+                    }
+                }
+                "--addr" => parsed.addr = value.parse().map_err(|err| invalid(&err))?,
+                "--root" => parsed.root = PathBuf::from(value),
+                "--drain-timeout" => {
+                    let secs = value.parse().map_err(|err| invalid(&err))?;
+                    parsed.drain_timeout = Duration::from_secs(secs);
+                }
+                _ => return Err(format!("unknown option {flag}")),
+            }
+        }
+        Ok(parsed)
+    }
+}
 
 fn main() {
-    // 监听地址：127.0.0.1:8787
-    let listener = TcpListener::bind("127.0.0.1:8787").unwrap();
-    let pool = ThreadPool::new(4);
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            if !err.is_empty() {
+                eprintln!("error: {err}");
+            }
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+    };
+    let listener = match TcpListener::bind(args.addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Error: cannot listen on {}: {}", args.addr, e);
+            process::exit(1);
+        }
+    };
+    let server = Server {
+        router: Arc::new(router(&args.root)),
+        shutdown: Shutdown::new(),
+        drain_timeout: args.drain_timeout,
+    };
+    let addr = listener.local_addr().unwrap_or(args.addr);
+    on_signal(server.shutdown.clone(), addr);
+    println!(
+        "Serving {} on http://{} ({} mode, {} workers)",
+        args.root.display(),
+        addr,
+        args.mode,
+        args.workers
+    );
+    let result = match args.mode {
+        Mode::Single => run_single(listener, server),
+        Mode::Pool => run_pool(listener, args.workers, server),
+        Mode::Tokio => run_tokio(listener, args.workers, server),
+        Mode::CustomRuntime => run_custom_runtime(listener, args.workers, server),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+    println!("Shutting down.");
+}
+
+/// Triggers `shutdown` on the first SIGINT or SIGTERM, and exits on the
+/// second. Connecting to `addr` wakes a blocked `accept` so it sees the
+/// shutdown.
+fn on_signal(shutdown: Shutdown, addr: SocketAddr) {
+    let wake = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::from((Ipv4Addr::LOCALHOST, addr.port()))
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::from((Ipv6Addr::LOCALHOST, addr.port()))
+        }
+        _ => addr,
+    };
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build the signal runtime");
+        runtime.block_on(async {
+            if let Err(e) = signalled().await {
+                println!("Error: cannot listen for signals: {}", e);
+                return;
+            }
+            println!("Stopping; open connections get to finish (signal again to exit now).");
+            shutdown.trigger();
+            let _ = TcpStream::connect(wake);
+            let _ = signalled().await;
+            process::exit(130);
+        });
+    });
+}
+
+#[cfg(unix)]
+async fn signalled() -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = interrupt.recv() => {}
+        _ = terminate.recv() => {}
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn signalled() -> io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+/// The request-handling core every mode shares.
+fn router(root: &Path) -> Router {
+    let files = StaticFiles::new(root)
+        .index("hello.html")
+        .not_found("404.html");
+    let sleepy = files.clone();
+    Router::new()
+        // Stands in for slow I/O. It blocks a thread in the `single` and
+        // `pool` modes, and only the task in the async ones.
+        .get("/sleep", move |mut req: Request<Vec<u8>>| {
+            let files = sleepy.clone();
+            async move {
+                runtime::sleep(Duration::from_secs(5)).await;
+                *req.uri_mut() = Uri::from_static("/");
+                files.call(req).await
+            }
+        })
+        .get("/*path", files)
+}
+
+/// What every mode shares: the handlers, and how to stop.
+#[derive(Clone)]
+struct Server {
+    router: Arc<Router>,
+    shutdown: Shutdown,
+    drain_timeout: Duration,
+}
+
+impl Server {
+    fn handle_connection(&self, stream: TcpStream) {
+        let (router, shutdown) = (&self.router, &self.shutdown);
+        if let Err(e) = serve_until(
+            stream,
+            router,
+            Limits::default(),
+            KeepAlive::default(),
+            shutdown,
+        ) {
+            println!("Error: {}", e);
+        }
+    }
+
+    async fn handle_connection_async(&self, io: impl AsyncRead + AsyncWrite + Unpin) {
+        let (router, shutdown) = (&self.router, &self.shutdown);
+        let result = serve_async_until(
+            io,
+            router,
+            Limits::default(),
+            KeepAlive::default(),
+            shutdown,
+        )
+        .await;
+        if let Err(e) = result {
+            println!("Error: {}", e);
+        }
+    }
+
+    /// The next connection, or `None` once shutdown is triggered.
+    async fn accept<S>(
+        &self,
+        accept: impl Future<Output = io::Result<S>>,
+    ) -> Option<io::Result<S>> {
+        future::or(async { Some(accept.await) }, async {
+            self.shutdown.triggered().await;
+            None
+        })
+        .await
+    }
+
+    /// Waits for the connections still open, and says if any outlast the
+    /// drain timeout.
+    fn drain(&self) -> bool {
+        if self.shutdown.drain(self.drain_timeout) {
+            return true;
+        }
+        println!(
+            "{} connections still open after {:?}; dropping them.",
+            self.shutdown.active(),
+            self.drain_timeout
+        );
+        false
+    }
+}
+
+fn run_single(listener: TcpListener, server: Server) -> io::Result<()> {
+    for stream in listener.incoming() {
+        if server.shutdown.is_triggered() {
+            break;
+        }
+        match stream {
+            Ok(stream) => server.handle_connection(stream),
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+fn run_pool(listener: TcpListener, workers: usize, server: Server) -> io::Result<()> {
+    let pool = ThreadPool::new(workers);
     for stream in listener.incoming() {
+        if server.shutdown.is_triggered() {
+            break;
+        }
         match stream {
             Ok(stream) => {
-                pool.execute(|| handle_connection(stream));
-            }
-            Err(e) => {
-                println!("Error: {}", e);
+                let guard = server.shutdown.track();
+                let server = server.clone();
+                pool.execute(move || {
+                    server.handle_connection(stream);
+                    drop(guard);
+                });
             }
+            Err(e) => println!("Error: {}", e),
         }
     }
-    println!("Shutting down.");
+    drop(listener);
+    if server.drain() {
+        drop(pool);
+    } else {
+        // Joining the workers would wait on the stuck connections; they
+        // end with the process instead.
+        mem::forget(pool);
+    }
+    Ok(())
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let buf_reader = BufReader::new(&mut stream);
-    let request_line = buf_reader
-        .lines()
-        .next()
-        .unwrap_or_else(|| Ok(String::from("")))
-        .unwrap();
-
-    let (status_line, filename) = match &request_line[..] {
-        "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "hello.html"),
-        "GET /sleep HTTP/1.1" => {
-            thread::sleep(Duration::from_secs(5));
-            ("HTTP/1.1 200 OK", "hello.html")
+fn run_tokio(listener: TcpListener, workers: usize, server: Server) -> io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(workers)
+        .enable_all()
+        .build()?;
+    listener.set_nonblocking(true)?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        while let Some(accepted) = server.accept(listener.accept()).await {
+            let (stream, _) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            };
+            let _ = stream.set_nodelay(true);
+            let guard = server.shutdown.track();
+            let server = server.clone();
+            tokio::spawn(async move {
+                server.handle_connection_async(stream.compat()).await;
+                drop(guard);
+            });
         }
-        _ => ("HTTP/1.1 404 NOT FOUND", "404.html"),
-    };
+        Ok::<_, io::Error>(())
+    })?;
+    if !server.drain() {
+        runtime.shutdown_background();
+    }
+    Ok(())
+}
 
-    let contents = fs::read_to_string(filename).unwrap();
-    let length = contents.len();
-    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}");
-    stream.write_all(response.as_bytes()).unwrap();
+fn run_custom_runtime(listener: TcpListener, workers: usize, server: Server) -> io::Result<()> {
+    Runtime::new().with_high_num(1).with_low_num(workers).run();
+    // Sockets are driven by smol's reactor, which wakes tasks on whichever
+    // executor polled them.
+    let listener = smol::Async::new(listener)?;
+    future::block_on(async {
+        while let Some(accepted) = server.accept(listener.accept()).await {
+            let (stream, _) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            };
+            let _ = stream.get_ref().set_nodelay(true);
+            let guard = server.shutdown.track();
+            let server = server.clone();
+            spawn_task!(async move {
+                server.handle_connection_async(stream).await;
+                drop(guard);
+            })
+            .detach();
+        }
+    });
+    drop(listener);
+    // The runtime's workers live as long as the process, so connections
+    // that outlast the drain end with it.
+    server.drain();
+    Ok(())
 }
 
 #[test]
 fn test() {
     assert_eq!(1, 1);
 }
+
+#[test]
+fn args_are_parsed() {
+    let args = |list: &[&str]| Args::parse(list.iter().map(|s| s.to_string()));
+    let parsed = args(&[]).unwrap();
+    assert_eq!(
+        (parsed.mode, parsed.workers, parsed.addr.port()),
+        (Mode::Pool, 4, 8787)
+    );
+    let parsed = args(&[
+        "--mode",
+        "custom-runtime",
+        "--workers",
+        "8",
+        "--root",
+        "www",
+    ])
+    .unwrap();
+    assert_eq!(parsed.mode, Mode::CustomRuntime);
+    assert_eq!(parsed.workers, 8);
+    assert_eq!(parsed.root, PathBuf::from("www"));
+    assert!(args(&["--mode", "fibers"]).is_err());
+    assert!(args(&["--workers", "0"]).is_err());
+    assert!(args(&["--addr"]).is_err());
+}
@@ -1,4 +1,5 @@
 use std::{
+    panic::{self, AssertUnwindSafe},
     sync::{mpsc, Arc, Mutex},
     thread,
 };
@@ -21,9 +22,11 @@ impl Worker {
             let message = receiver.lock().unwrap().recv();
             match message {
                 Ok(job) => {
-                    println!("Worker {id} got a job; executing.");
-
-                    job();
+                    // A panicking job must not take its worker down with it;
+                    // the panic hook has already reported it.
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        eprintln!("Worker {id} recovered from a panicking job.");
+                    }
                 }
                 Err(_) => {
                     println!("Worker {id} disconnected; shutting down.");
@@ -46,6 +49,9 @@ impl ThreadPool {
     /// # Panics
     ///
     /// The `new` function will panic if the size is zero.
+    ///
+    /// A job that panics does not stop its worker; the next job runs as
+    /// usual.
     pub fn new(size: usize) -> ThreadPool {
         assert!(size > 0);
 
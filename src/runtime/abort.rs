@@ -0,0 +1,103 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use super::join::JoinError;
+use super::time::Sleep;
+
+/// What a task shares with its [`JoinHandle`] and [`AbortHandle`]s.
+///
+/// [`JoinHandle`]: super::JoinHandle
+#[derive(Default)]
+pub(crate) struct AbortState {
+    aborted: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+    /// The task's `JoinHandle` was detached or dropped, so nothing will
+    /// see the task's output.
+    pub(crate) unobserved: AtomicBool,
+}
+
+/// Cancels a task from anywhere, without owning its [`JoinHandle`].
+///
+/// The task stops the next time it would be polled and its handle resolves
+/// to a cancelled [`JoinError`]. A poll already in progress is not
+/// interrupted.
+///
+/// [`JoinHandle`]: super::JoinHandle
+#[derive(Clone)]
+pub struct AbortHandle {
+    state: Arc<AbortState>,
+}
+
+impl AbortHandle {
+    pub(crate) fn new(state: Arc<AbortState>) -> Self {
+        Self { state }
+    }
+
+    pub fn abort(&self) {
+        self.state.aborted.store(true, Ordering::Release);
+        if let Some(waker) = self.state.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.state.aborted.load(Ordering::Acquire)
+    }
+}
+
+impl std::fmt::Debug for AbortHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AbortHandle")
+            .field("aborted", &self.is_aborted())
+            .finish()
+    }
+}
+
+/// Ends a task early when it is aborted or its deadline passes.
+pub(crate) struct Abortable<F> {
+    future: F,
+    state: Arc<AbortState>,
+    deadline: Option<Sleep>,
+}
+
+impl<F> Abortable<F> {
+    pub(crate) fn new(future: F, state: Arc<AbortState>, deadline: Option<Sleep>) -> Self {
+        Self {
+            future,
+            state,
+            deadline,
+        }
+    }
+}
+
+impl<F, T> Future for Abortable<F>
+where
+    F: Future<Output = Result<T, JoinError>>,
+{
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`; `Sleep` is `Unpin`.
+        let this = unsafe { self.get_unchecked_mut() };
+
+        if this.state.aborted.load(Ordering::Acquire) {
+            return Poll::Ready(Err(JoinError::cancelled()));
+        }
+        if let Some(deadline) = &mut this.deadline {
+            if Pin::new(deadline).poll(cx).is_ready() {
+                return Poll::Ready(Err(JoinError::timed_out()));
+            }
+        }
+        *this.state.waker.lock().unwrap() = Some(cx.waker().clone());
+        // `abort` may have run between the check above and storing the waker.
+        if this.state.aborted.load(Ordering::Acquire) {
+            return Poll::Ready(Err(JoinError::cancelled()));
+        }
+
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        future.poll(cx)
+    }
+}
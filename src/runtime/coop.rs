@@ -0,0 +1,74 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Number of budget units a task may spend in one poll.
+pub(crate) const TASK_BUDGET: u32 = 128;
+
+thread_local! {
+    /// `None` outside of a runtime worker, where budgeting is disabled.
+    static BUDGET: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Runs `f` (one poll of a task) with a fresh budget.
+pub(crate) fn budgeted<R>(f: impl FnOnce() -> R) -> R {
+    struct Reset(Option<u32>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            BUDGET.with(|budget| budget.set(self.0));
+        }
+    }
+
+    let _reset = Reset(BUDGET.with(|budget| budget.replace(Some(TASK_BUDGET))));
+    f()
+}
+
+/// Spends one unit of the current task's budget.
+///
+/// Returns `Pending` once the budget is exhausted, after waking the task so
+/// it goes to the back of its run queue. Leaf futures that can complete
+/// without ever returning `Pending` (the ones in [`crate::sync`], for
+/// example) call this first so a busy loop over them still yields.
+pub fn poll_proceed(cx: &mut Context<'_>) -> Poll<()> {
+    BUDGET.with(|budget| match budget.get() {
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(n) => {
+            budget.set(Some(n - 1));
+            Poll::Ready(())
+        }
+        None => Poll::Ready(()),
+    })
+}
+
+/// Consumes one unit of budget, yielding if it has run out.
+pub async fn consume_budget() {
+    std::future::poll_fn(poll_proceed).await
+}
+
+/// Yields once, rescheduling the current task behind everything already
+/// queued.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
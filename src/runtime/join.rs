@@ -0,0 +1,287 @@
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe, Location};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{ready, Context, Poll};
+
+use async_task::FallibleTask;
+use once_cell::sync::Lazy;
+
+use super::abort::{AbortHandle, AbortState};
+
+pub(crate) type PanicHandler = Arc<dyn Fn(&TaskPanic<'_>) + Send + Sync>;
+
+pub(crate) static PANIC_HANDLER: Lazy<RwLock<Option<PanicHandler>>> =
+    Lazy::new(|| RwLock::new(None));
+pub(crate) static ABORT_ON_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// What the runtime does when a task panics and nothing can handle it:
+/// its [`JoinHandle`] was detached or dropped before the panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnhandledPanic {
+    /// Keep running; the panic is only reported to the panic handler.
+    #[default]
+    Ignore,
+    /// Abort the whole process after the panic handler has run. Panics in
+    /// tasks whose handle is still held surface as a [`JoinError`] instead.
+    ShutdownProcess,
+}
+
+/// A panic caught while polling a task, passed to the runtime's panic
+/// handler.
+pub struct TaskPanic<'a> {
+    pub task_id: u64,
+    pub location: &'static Location<'static>,
+    pub payload: &'a (dyn Any + Send),
+}
+
+impl TaskPanic<'_> {
+    /// The panic message, if the payload is a string.
+    pub fn message(&self) -> Option<&str> {
+        panic_message(self.payload)
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+/// Why a task did not produce its output.
+pub struct JoinError {
+    repr: Repr,
+}
+
+enum Repr {
+    Panic(Box<dyn Any + Send>),
+    Cancelled,
+    TimedOut,
+}
+
+impl JoinError {
+    pub(crate) fn cancelled() -> Self {
+        Self {
+            repr: Repr::Cancelled,
+        }
+    }
+
+    pub(crate) fn timed_out() -> Self {
+        Self {
+            repr: Repr::TimedOut,
+        }
+    }
+
+    pub fn is_panic(&self) -> bool {
+        matches!(self.repr, Repr::Panic(_))
+    }
+
+    /// Whether the task was cancelled or aborted.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.repr, Repr::Cancelled)
+    }
+
+    /// Whether the task was stopped by its `spawn_with_timeout` deadline.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.repr, Repr::TimedOut)
+    }
+
+    /// The panic payload, for use with [`std::panic::resume_unwind`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the task did not panic.
+    pub fn into_panic(self) -> Box<dyn Any + Send> {
+        self.try_into_panic()
+            .unwrap_or_else(|_| panic!("`JoinError` is not a panic"))
+    }
+
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send>, Self> {
+        match self.repr {
+            Repr::Panic(payload) => Ok(payload),
+            _ => Err(self),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Panic(payload) => match panic_message(payload.as_ref()) {
+                Some(message) => write!(f, "task panicked: {message}"),
+                None => f.write_str("task panicked"),
+            },
+            Repr::Cancelled => f.write_str("task was cancelled"),
+            Repr::TimedOut => f.write_str("task timed out"),
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Panic(payload) => f
+                .debug_tuple("Panic")
+                .field(&panic_message(payload.as_ref()).unwrap_or("..."))
+                .finish(),
+            Repr::Cancelled => f.write_str("Cancelled"),
+            Repr::TimedOut => f.write_str("TimedOut"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+/// Turns a panic inside the task into `Err(JoinError)` instead of letting it
+/// unwind into the worker.
+pub(crate) struct CatchUnwind<F> {
+    future: F,
+    task_id: u64,
+    location: &'static Location<'static>,
+    state: Arc<AbortState>,
+}
+
+impl<F> CatchUnwind<F> {
+    pub(crate) fn new(
+        future: F,
+        task_id: u64,
+        location: &'static Location<'static>,
+        state: Arc<AbortState>,
+    ) -> Self {
+        Self {
+            future,
+            task_id,
+            location,
+            state,
+        }
+    }
+}
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        match catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(payload) => {
+                tracing::error!(
+                    panic = panic_message(payload.as_ref()).unwrap_or("<non-string payload>"),
+                    "task panicked"
+                );
+                let panic = TaskPanic {
+                    task_id: this.task_id,
+                    location: this.location,
+                    payload: payload.as_ref(),
+                };
+                let handler = PANIC_HANDLER.read().unwrap().clone();
+                if let Some(handler) = handler {
+                    handler(&panic);
+                }
+                if ABORT_ON_PANIC.load(Ordering::Relaxed)
+                    && this.state.unobserved.load(Ordering::Acquire)
+                {
+                    eprintln!(
+                        "task {} spawned at {} panicked with no handle to report to; aborting",
+                        this.task_id, this.location
+                    );
+                    std::process::abort();
+                }
+                Poll::Ready(Err(JoinError {
+                    repr: Repr::Panic(payload),
+                }))
+            }
+        }
+    }
+}
+
+/// An owned permission to await a spawned task.
+///
+/// Resolves to the task's output, or to a [`JoinError`] if it panicked or
+/// was cancelled. Dropping the handle cancels the task; call
+/// [`detach`](JoinHandle::detach) to let it run in the background.
+pub struct JoinHandle<T> {
+    task: Option<FallibleTask<Result<T, JoinError>>>,
+    abort: Arc<AbortState>,
+}
+
+impl<T> JoinHandle<T> {
+    pub(crate) fn new(
+        task: async_task::Task<Result<T, JoinError>>,
+        abort: Arc<AbortState>,
+    ) -> Self {
+        Self {
+            task: Some(task.fallible()),
+            abort,
+        }
+    }
+
+    /// A cloneable handle that can abort the task from elsewhere.
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle::new(self.abort.clone())
+    }
+
+    /// Aborts the task; awaiting the handle then yields a cancelled
+    /// [`JoinError`] unless the task had already finished.
+    pub fn abort(&self) {
+        self.abort_handle().abort();
+    }
+
+    /// Lets the task keep running after the handle is dropped.
+    pub fn detach(self) {
+        // Dropping the handle marks the task unobserved before it is let go.
+        let mut this = self;
+        if let Some(task) = this.task.take() {
+            this.abort.unobserved.store(true, Ordering::Release);
+            task.detach();
+        }
+    }
+
+    /// Cancels the task and waits for it to stop. Returns the output if the
+    /// task had already completed.
+    pub async fn cancel(mut self) -> Option<Result<T, JoinError>> {
+        self.task.take()?.cancel().await
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.as_ref().is_none_or(|task| task.is_finished())
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let task = self
+            .task
+            .as_mut()
+            .expect("`JoinHandle` polled after completion");
+        let output = ready!(Pin::new(task).poll(cx));
+        self.task = None;
+        Poll::Ready(output.unwrap_or_else(|| Err(JoinError::cancelled())))
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        // Detached, or dropped before the output was taken.
+        if self.task.is_some() {
+            self.abort.unobserved.store(true, Ordering::Release);
+        }
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+use std::panic::Location;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+
+use super::per_core::CORES;
+use super::task::{self, TASKS};
+use super::{FutureType, HIGH_CHANNEL, LOW_CHANNEL};
+
+/// A point-in-time view of the runtime's counters.
+#[derive(Debug, Clone)]
+pub struct RuntimeMetrics {
+    /// Runnables waiting in the high priority queue.
+    pub high_queue_depth: usize,
+    /// Runnables waiting in the low priority queue.
+    pub low_queue_depth: usize,
+    /// `(high, low)` queue depths of each worker in thread-per-core mode,
+    /// which are also included in the totals above.
+    pub core_queue_depths: Vec<(usize, usize)>,
+    /// Tasks spawned and not yet completed or dropped.
+    pub live_tasks: usize,
+    pub spawned_tasks: u64,
+    pub completed_tasks: u64,
+    pub total_polls: u64,
+    /// Wall-clock time spent inside `Future::poll` across all tasks.
+    pub total_busy: Duration,
+    /// Polls that took longer than the long poll threshold.
+    pub long_polls: u64,
+}
+
+impl RuntimeMetrics {
+    pub(crate) fn collect() -> Self {
+        // Peek without forcing the per-core workers to start.
+        let core_queue_depths: Vec<_> = Lazy::get(&CORES)
+            .map(|cores| cores.iter().map(|core| core.queue_depths()).collect())
+            .unwrap_or_default();
+        Self {
+            high_queue_depth: HIGH_CHANNEL.1.len()
+                + core_queue_depths
+                    .iter()
+                    .map(|(high, _)| high)
+                    .sum::<usize>(),
+            low_queue_depth: LOW_CHANNEL.1.len()
+                + core_queue_depths.iter().map(|(_, low)| low).sum::<usize>(),
+            core_queue_depths,
+            live_tasks: TASKS.lock().unwrap().len(),
+            spawned_tasks: task::SPAWNED.load(Ordering::Relaxed),
+            completed_tasks: task::COMPLETED.load(Ordering::Relaxed),
+            total_polls: task::POLLS.load(Ordering::Relaxed),
+            total_busy: Duration::from_nanos(task::BUSY_NANOS.load(Ordering::Relaxed)),
+            long_polls: task::LONG_POLLS.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Average time per poll, or zero if nothing has been polled yet.
+    pub fn mean_poll_duration(&self) -> Duration {
+        if self.total_polls == 0 {
+            return Duration::ZERO;
+        }
+        self.total_busy / self.total_polls.min(u32::MAX as u64) as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    /// Waiting to be woken.
+    Idle,
+    /// Woken and sitting in a run queue.
+    Scheduled,
+    /// Being polled by a worker right now.
+    Running,
+}
+
+impl fmt::Display for TaskState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            TaskState::Idle => "idle",
+            TaskState::Scheduled => "scheduled",
+            TaskState::Running => "running",
+        };
+        f.pad(s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskSnapshot {
+    pub id: u64,
+    pub priority: FutureType,
+    /// Where `spawn_task!` was called.
+    pub location: &'static Location<'static>,
+    pub state: TaskState,
+    pub polls: u64,
+    pub busy: Duration,
+    pub max_poll: Duration,
+    pub long_polls: u64,
+    pub age: Duration,
+}
+
+/// Every live task at the time of the dump, ordered by task id.
+#[derive(Debug, Clone)]
+pub struct TaskDump {
+    pub tasks: Vec<TaskSnapshot>,
+}
+
+impl TaskDump {
+    pub(crate) fn collect() -> Self {
+        let mut tasks: Vec<_> = TASKS
+            .lock()
+            .unwrap()
+            .values()
+            .map(|header| header.snapshot())
+            .collect();
+        tasks.sort_by_key(|task| task.id);
+        Self { tasks }
+    }
+}
+
+impl fmt::Display for TaskDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} live task(s)", self.tasks.len())?;
+        for task in &self.tasks {
+            write!(
+                f,
+                "  #{:<5} {:<4} {:<9} polls={:<6} busy={:?} max_poll={:?} spawned at {}",
+                task.id,
+                format!("{:?}", task.priority),
+                task.state,
+                task.polls,
+                task.busy,
+                task.max_poll,
+                task.location,
+            )?;
+            if task.long_polls > 0 {
+                write!(f, " [WARN: {} long poll(s)]", task.long_polls)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+/*
+dependencies added to Cargo.toml
+async-task = "4.4.0"
+futures-lite = "1.12.0"
+once_cell = "1.17.1"
+flume = "0.10.14"
+ */
+
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use std::{future::Future, panic::catch_unwind, thread};
+
+use async_task::{Runnable, ScheduleInfo, WithInfo};
+use flume::{Receiver, Sender};
+use futures_lite::future;
+use once_cell::sync::{Lazy, OnceCell};
+
+mod abort;
+pub(crate) mod coop;
+mod join;
+mod metrics;
+mod per_core;
+mod task;
+mod time;
+mod trace;
+
+pub use abort::AbortHandle;
+use abort::{AbortState, Abortable};
+pub use coop::{consume_budget, poll_proceed, yield_now, YieldNow};
+use join::CatchUnwind;
+pub use join::{JoinError, JoinHandle, TaskPanic, UnhandledPanic};
+pub use metrics::{RuntimeMetrics, TaskDump, TaskSnapshot, TaskState};
+pub use task::current_task_id;
+use task::{TaskHeader, Tracked};
+pub use time::{sleep, timeout, Elapsed, Sleep, Timeout};
+pub use trace::{InSpan, Named, TaskInstrument};
+
+#[macro_export]
+macro_rules! spawn_task {
+    ($future:expr) => {
+        spawn_task_function($future, FutureType::Low)
+    };
+    ($future:expr, $order:expr) => {
+        spawn_task_function($future, $order)
+    };
+}
+
+#[macro_export]
+macro_rules! join {
+    ($($future:expr),*) => {
+        {
+            let results: Vec<_> = vec![$(future::block_on($future)),*];
+            results
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! try_join {
+    ($($future:expr),*) => {
+        {
+            let mut results = Vec::new();
+            $(
+                let result = catch_unwind(|| {
+                    future::block_on($future)
+                });
+                results.push(result);
+            )*
+            results
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FutureType {
+    High,
+    Low,
+}
+
+/// How worker threads are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    /// Shared High and Low queues, each with its own group of workers.
+    Priority,
+    /// One worker pinned to each core, owning its own High and Low queues.
+    /// Work only moves to another core when the home core is overloaded.
+    ThreadPerCore,
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    flavor: Flavor,
+    high_num: usize,
+    low_num: usize,
+    cores: usize,
+}
+
+/// Fixed by the first [`Runtime::run`], or read from `HIGH_NUM`/`LOW_NUM`
+/// and the defaults if a task is spawned before that.
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+fn config() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        let mut runtime = Runtime::new();
+        let from_env = |name| std::env::var(name).ok()?.parse::<usize>().ok();
+        if let Some(num) = from_env("HIGH_NUM") {
+            runtime.high_num = num;
+        }
+        if let Some(num) = from_env("LOW_NUM") {
+            runtime.low_num = num;
+        }
+        runtime.config()
+    })
+}
+
+static HIGH_CHANNEL: Lazy<(Sender<Runnable>, Receiver<Runnable>)> =
+    Lazy::new(flume::unbounded::<Runnable>);
+static LOW_CHANNEL: Lazy<(Sender<Runnable>, Receiver<Runnable>)> =
+    Lazy::new(flume::unbounded::<Runnable>);
+
+/// After this many runnables in a row from its own queue, a worker takes
+/// from the other queue first, so a queue that never drains (for example
+/// one holding a self-waking task) cannot starve the other priority.
+const FAIRNESS_INTERVAL: u32 = 31;
+
+fn worker_loop(own: Receiver<Runnable>, other: Receiver<Runnable>) {
+    let mut streak = 0;
+    loop {
+        let runnable = if streak >= FAIRNESS_INTERVAL {
+            streak = 0;
+            other.try_recv().or_else(|_| own.try_recv())
+        } else {
+            match own.try_recv() {
+                Ok(runnable) => {
+                    streak += 1;
+                    Ok(runnable)
+                }
+                Err(_) => {
+                    streak = 0;
+                    other.try_recv()
+                }
+            }
+        };
+        match runnable {
+            Ok(runnable) => run(runnable),
+            Err(_) => {
+                // Park until either queue has work, checking back regularly.
+                if let Ok(Ok(runnable)) = flume::Selector::new()
+                    .recv(&own, |runnable| runnable)
+                    .recv(&other, |runnable| runnable)
+                    .wait_timeout(Duration::from_millis(100))
+                {
+                    run(runnable);
+                }
+            }
+        }
+    }
+}
+
+fn run(runnable: Runnable) {
+    let _ = catch_unwind(|| coop::budgeted(|| runnable.run()));
+}
+
+#[track_caller]
+pub fn spawn_task_function<F, T>(future: F, order: FutureType) -> JoinHandle<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_inner(future, order, None, Location::caller())
+}
+
+/// Like [`spawn_task_function`], but the task is cancelled once `timeout`
+/// has elapsed and its handle resolves to a [`JoinError`] for which
+/// [`JoinError::is_timeout`] is true.
+#[track_caller]
+pub fn spawn_with_timeout<F, T>(future: F, order: FutureType, timeout: Duration) -> JoinHandle<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    spawn_inner(future, order, Some(timeout), Location::caller())
+}
+
+fn spawn_inner<F, T>(
+    future: F,
+    order: FutureType,
+    timeout: Option<Duration>,
+    location: &'static Location<'static>,
+) -> JoinHandle<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    static HIGH_QUEUE: Lazy<flume::Sender<Runnable>> = Lazy::new(|| {
+        for _ in 0..config().high_num {
+            let high_receiver = HIGH_CHANNEL.1.clone();
+            let low_receiver = LOW_CHANNEL.1.clone();
+            thread::spawn(move || worker_loop(high_receiver, low_receiver));
+        }
+        HIGH_CHANNEL.0.clone()
+    });
+    static LOW_QUEUE: Lazy<flume::Sender<Runnable>> = Lazy::new(|| {
+        for _ in 0..config().low_num {
+            let high_receiver = HIGH_CHANNEL.1.clone();
+            let low_receiver = LOW_CHANNEL.1.clone();
+            thread::spawn(move || worker_loop(low_receiver, high_receiver));
+        }
+        LOW_CHANNEL.0.clone()
+    });
+
+    let header = TaskHeader::register(order, location);
+    let abort = std::sync::Arc::new(AbortState::default());
+    let future = CatchUnwind::new(future, header.id, header.location, abort.clone());
+    let future = Abortable::new(future, abort.clone(), timeout.map(sleep));
+    let future = match crate::test::try_spawn(Tracked::new(future, header.clone())) {
+        Ok(task) => return JoinHandle::new(task, abort),
+        Err(future) => future,
+    };
+    let flavor = config().flavor;
+    let home = match flavor {
+        Flavor::Priority => 0,
+        Flavor::ThreadPerCore => per_core::home(),
+    };
+    let schedule = {
+        let header = header.clone();
+        WithInfo(move |runnable, info: ScheduleInfo| match (flavor, order) {
+            (Flavor::Priority, FutureType::High) => {
+                header.on_schedule(info, "high");
+                HIGH_QUEUE.send(runnable).unwrap()
+            }
+            (Flavor::Priority, FutureType::Low) => {
+                header.on_schedule(info, "low");
+                LOW_QUEUE.send(runnable).unwrap()
+            }
+            (Flavor::ThreadPerCore, order) => {
+                header.on_schedule(info, "core");
+                per_core::schedule(runnable, order, home)
+            }
+        })
+    };
+    let (runnalbe, task) = async_task::spawn(future, schedule);
+    runnalbe.schedule();
+    JoinHandle::new(task, abort)
+}
+
+pub struct CounterFuture {
+    count: u32,
+}
+
+impl Future for CounterFuture {
+    type Output = u32;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.count += 1;
+        println!("polling with result: {}", self.count);
+        thread::sleep(Duration::from_secs(1));
+        if self.count < 3 {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            Poll::Ready(self.count)
+        }
+    }
+}
+
+pub async fn async_fn() {
+    std::thread::sleep(Duration::from_secs(1));
+    println!("async fn");
+}
+
+pub struct Runtime {
+    flavor: Flavor,
+    high_num: usize,
+    low_num: usize,
+    cores: usize,
+    long_poll_threshold: Duration,
+    panic_handler: Option<join::PanicHandler>,
+    unhandled_panic: UnhandledPanic,
+}
+
+impl Runtime {
+    /// A priority runtime sized to the machine: one Low worker and the
+    /// remaining cores, less one for the rest of the process, as High
+    /// workers. Always at least one of each.
+    pub fn new() -> Self {
+        let num_cores = available_cores();
+        Self {
+            flavor: Flavor::Priority,
+            high_num: num_cores.saturating_sub(2).max(1),
+            low_num: 1,
+            cores: num_cores,
+            long_poll_threshold: Duration::from_millis(100),
+            panic_handler: None,
+            unhandled_panic: UnhandledPanic::Ignore,
+        }
+    }
+    /// A thread-per-core runtime with one pinned worker per available core.
+    pub fn thread_per_core() -> Self {
+        Self {
+            flavor: Flavor::ThreadPerCore,
+            ..Self::new()
+        }
+    }
+    /// Number of pinned workers in [`Flavor::ThreadPerCore`] mode. Zero is
+    /// treated as one.
+    pub fn with_cores(mut self, num: usize) -> Self {
+        self.cores = num.max(1);
+        self
+    }
+    pub fn with_high_num(mut self, num: usize) -> Self {
+        self.high_num = num;
+        self
+    }
+    pub fn with_low_num(mut self, num: usize) -> Self {
+        self.low_num = num;
+        self
+    }
+    /// Polls that take at least this long are logged as warnings and
+    /// counted in [`RuntimeMetrics::long_polls`].
+    pub fn with_long_poll_threshold(mut self, threshold: Duration) -> Self {
+        self.long_poll_threshold = threshold;
+        self
+    }
+    /// Called on the worker thread whenever a task panics, before the panic
+    /// is handed to the task's [`JoinHandle`].
+    pub fn with_panic_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&TaskPanic<'_>) + Send + Sync + 'static,
+    {
+        self.panic_handler = Some(std::sync::Arc::new(handler));
+        self
+    }
+    pub fn with_unhandled_panic(mut self, behavior: UnhandledPanic) -> Self {
+        self.unhandled_panic = behavior;
+        self
+    }
+    pub fn flavor(&self) -> Flavor {
+        self.flavor
+    }
+    fn config(&self) -> Config {
+        Config {
+            flavor: self.flavor,
+            high_num: self.high_num,
+            low_num: self.low_num,
+            cores: self.cores,
+        }
+    }
+    /// Starts the workers. Worker layout is fixed by the first call; later
+    /// calls only update the panic and long poll settings.
+    pub fn run(&self) {
+        if CONFIG.set(self.config()).is_err() {
+            tracing::warn!("runtime workers already started; keeping their layout");
+        }
+        task::LONG_POLL_THRESHOLD_NANOS.store(
+            self.long_poll_threshold.as_nanos() as u64,
+            Ordering::Relaxed,
+        );
+        *join::PANIC_HANDLER.write().unwrap() = self.panic_handler.clone();
+        join::ABORT_ON_PANIC.store(
+            self.unhandled_panic == UnhandledPanic::ShutdownProcess,
+            Ordering::Relaxed,
+        );
+
+        let high = spawn_task_function(async {}, FutureType::High);
+        let low = spawn_task_function(async {}, FutureType::Low);
+        join!(high, low);
+    }
+    /// Queue depths and task counters for the whole runtime.
+    pub fn metrics(&self) -> RuntimeMetrics {
+        RuntimeMetrics::collect()
+    }
+    /// Lists every live task with its spawn location, priority and state.
+    pub fn task_dump(&self) -> TaskDump {
+        TaskDump::collect()
+    }
+}
+
+/// Cores this process may run on, falling back to one when the platform
+/// cannot tell.
+fn available_cores() -> usize {
+    core_affinity::get_core_ids()
+        .map(|ids| ids.len())
+        .filter(|&n| n > 0)
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BackgroundProcess;
+
+impl Future for BackgroundProcess {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        println!("Background process running");
+        std::thread::sleep(Duration::from_secs(1));
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,97 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use async_task::Runnable;
+use flume::{Receiver, Sender};
+use once_cell::sync::Lazy;
+
+use super::{config, worker_loop, FutureType};
+
+/// A core starts handing new work to its least busy peer once this many
+/// runnables are waiting in its own queues.
+const SPILL_THRESHOLD: usize = 64;
+
+pub(crate) struct Core {
+    high: (Sender<Runnable>, Receiver<Runnable>),
+    low: (Sender<Runnable>, Receiver<Runnable>),
+}
+
+impl Core {
+    pub(crate) fn queue_depths(&self) -> (usize, usize) {
+        (self.high.1.len(), self.low.1.len())
+    }
+
+    fn load(&self) -> usize {
+        self.high.1.len() + self.low.1.len()
+    }
+}
+
+pub(crate) static CORES: Lazy<Vec<Core>> = Lazy::new(|| {
+    let workers = config().cores;
+    let core_ids = core_affinity::get_core_ids().unwrap_or_default();
+    let cores: Vec<Core> = (0..workers)
+        .map(|_| Core {
+            high: flume::unbounded(),
+            low: flume::unbounded(),
+        })
+        .collect();
+
+    for (index, core) in cores.iter().enumerate() {
+        let high = core.high.1.clone();
+        let low = core.low.1.clone();
+        // With more workers than cores some share a core; with no topology
+        // information at all they simply run unpinned.
+        let core_id = (!core_ids.is_empty()).then(|| core_ids[index % core_ids.len()]);
+        thread::Builder::new()
+            .name(format!("runtime-core-{index}"))
+            .spawn(move || {
+                if let Some(core_id) = core_id {
+                    if !core_affinity::set_for_current(core_id) {
+                        tracing::warn!(worker = index, "failed to pin worker to its core");
+                    }
+                }
+                CURRENT.with(|current| current.set(Some(index)));
+                worker_loop(high, low);
+            })
+            .expect("failed to spawn runtime worker");
+    }
+    cores
+});
+
+thread_local! {
+    /// Index of the core this worker thread owns.
+    static CURRENT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// The core a new task should live on: the spawning worker's own core, or
+/// round-robin when spawned from outside the runtime.
+pub(crate) fn home() -> usize {
+    CURRENT
+        .with(Cell::get)
+        .unwrap_or_else(|| NEXT.fetch_add(1, Ordering::Relaxed) % CORES.len())
+}
+
+/// Queues `runnable` on its home core, spilling to the least loaded core
+/// only when home is overloaded.
+pub(crate) fn schedule(runnable: Runnable, order: FutureType, home: usize) {
+    let cores = &*CORES;
+    let mut target = home;
+    if cores[home].load() >= SPILL_THRESHOLD {
+        let (least, core) = cores
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, core)| core.load())
+            .unwrap();
+        if core.load() < cores[home].load() {
+            target = least;
+        }
+    }
+    let core = &cores[target];
+    match order {
+        FutureType::High => core.high.0.send(runnable).unwrap(),
+        FutureType::Low => core.low.0.send(runnable).unwrap(),
+    }
+}
@@ -0,0 +1,209 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_task::ScheduleInfo;
+use once_cell::sync::Lazy;
+use tracing::Span;
+
+use super::metrics::{TaskSnapshot, TaskState};
+use super::FutureType;
+
+/// Every task that has been spawned and not yet completed or dropped.
+pub(crate) static TASKS: Lazy<Mutex<HashMap<u64, Arc<TaskHeader>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+pub(crate) static SPAWNED: AtomicU64 = AtomicU64::new(0);
+pub(crate) static COMPLETED: AtomicU64 = AtomicU64::new(0);
+pub(crate) static POLLS: AtomicU64 = AtomicU64::new(0);
+pub(crate) static BUSY_NANOS: AtomicU64 = AtomicU64::new(0);
+pub(crate) static LONG_POLLS: AtomicU64 = AtomicU64::new(0);
+pub(crate) static LONG_POLL_THRESHOLD_NANOS: AtomicU64 = AtomicU64::new(100_000_000);
+
+thread_local! {
+    /// The task being polled on this thread.
+    static CURRENT: RefCell<Option<Arc<TaskHeader>>> = const { RefCell::new(None) };
+}
+
+/// Span of the task currently being polled on this thread, if any.
+pub(crate) fn current_span() -> Option<Span> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|header| header.span.clone()))
+}
+
+/// Id of the runtime task being polled on the calling thread.
+pub fn current_task_id() -> Option<u64> {
+    CURRENT.with(|current| current.borrow().as_ref().map(|header| header.id))
+}
+
+const IDLE: u8 = 0;
+const SCHEDULED: u8 = 1;
+const RUNNING: u8 = 2;
+
+/// Bookkeeping shared between a task's future, its schedule function and
+/// the registry.
+pub(crate) struct TaskHeader {
+    pub(crate) id: u64,
+    pub(crate) priority: FutureType,
+    pub(crate) location: &'static Location<'static>,
+    /// Entered on every poll; lifecycle events are recorded under it.
+    pub(crate) span: Span,
+    spawned_at: Instant,
+    state: AtomicU8,
+    polls: AtomicU64,
+    busy_nanos: AtomicU64,
+    max_poll_nanos: AtomicU64,
+    long_polls: AtomicU64,
+}
+
+impl TaskHeader {
+    pub(crate) fn register(
+        priority: FutureType,
+        location: &'static Location<'static>,
+    ) -> Arc<TaskHeader> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let span = tracing::info_span!(
+            "task",
+            task.id = id,
+            task.priority = ?priority,
+            task.location = %location,
+            task.name = tracing::field::Empty,
+        );
+        tracing::debug!(parent: &span, "task spawned");
+        let header = Arc::new(TaskHeader {
+            id,
+            priority,
+            location,
+            span,
+            spawned_at: Instant::now(),
+            state: AtomicU8::new(IDLE),
+            polls: AtomicU64::new(0),
+            busy_nanos: AtomicU64::new(0),
+            max_poll_nanos: AtomicU64::new(0),
+            long_polls: AtomicU64::new(0),
+        });
+        SPAWNED.fetch_add(1, Ordering::Relaxed);
+        TASKS.lock().unwrap().insert(header.id, header.clone());
+        header
+    }
+
+    /// Called from the schedule function, i.e. on the initial schedule and
+    /// on every wake after that.
+    pub(crate) fn on_schedule(&self, info: ScheduleInfo, queue: &'static str) {
+        if self.polls.load(Ordering::Relaxed) > 0 {
+            tracing::trace!(
+                parent: &self.span,
+                woken_while_running = info.woken_while_running,
+                "task woken"
+            );
+        }
+        self.state.store(SCHEDULED, Ordering::Release);
+        tracing::trace!(parent: &self.span, queue, "task scheduled");
+    }
+
+    pub(crate) fn snapshot(&self) -> TaskSnapshot {
+        TaskSnapshot {
+            id: self.id,
+            priority: self.priority,
+            location: self.location,
+            state: match self.state.load(Ordering::Acquire) {
+                SCHEDULED => TaskState::Scheduled,
+                RUNNING => TaskState::Running,
+                _ => TaskState::Idle,
+            },
+            polls: self.polls.load(Ordering::Relaxed),
+            busy: Duration::from_nanos(self.busy_nanos.load(Ordering::Relaxed)),
+            max_poll: Duration::from_nanos(self.max_poll_nanos.load(Ordering::Relaxed)),
+            long_polls: self.long_polls.load(Ordering::Relaxed),
+            age: self.spawned_at.elapsed(),
+        }
+    }
+
+    fn record_poll(&self, elapsed: Duration) {
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        self.busy_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_poll_nanos.fetch_max(nanos, Ordering::Relaxed);
+        POLLS.fetch_add(1, Ordering::Relaxed);
+        BUSY_NANOS.fetch_add(nanos, Ordering::Relaxed);
+
+        if nanos >= LONG_POLL_THRESHOLD_NANOS.load(Ordering::Relaxed) {
+            self.long_polls.fetch_add(1, Ordering::Relaxed);
+            LONG_POLLS.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                parent: &self.span,
+                task.id = self.id,
+                task.location = %self.location,
+                poll = ?elapsed,
+                "task poll exceeded the long poll threshold"
+            );
+        }
+    }
+}
+
+/// Wraps a spawned future to keep its [`TaskHeader`] up to date.
+pub(crate) struct Tracked<F> {
+    future: F,
+    header: Arc<TaskHeader>,
+    completed: bool,
+}
+
+impl<F> Tracked<F> {
+    pub(crate) fn new(future: F, header: Arc<TaskHeader>) -> Self {
+        Self {
+            future,
+            header,
+            completed: false,
+        }
+    }
+}
+
+impl<F: Future> Future for Tracked<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `future` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let _enter = this.header.span.enter();
+        let previous = CURRENT.with(|current| current.replace(Some(this.header.clone())));
+        this.header.state.store(RUNNING, Ordering::Release);
+        let start = Instant::now();
+        let poll = future.poll(cx);
+        this.header.record_poll(start.elapsed());
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+        // A wake during the poll may already have marked the task scheduled.
+        let _ =
+            this.header
+                .state
+                .compare_exchange(RUNNING, IDLE, Ordering::AcqRel, Ordering::Acquire);
+
+        if poll.is_ready() {
+            COMPLETED.fetch_add(1, Ordering::Relaxed);
+            tracing::debug!(
+                polls = this.header.polls.load(Ordering::Relaxed),
+                busy = ?Duration::from_nanos(this.header.busy_nanos.load(Ordering::Relaxed)),
+                "task completed"
+            );
+            this.completed = true;
+        }
+        poll
+    }
+}
+
+impl<F> Drop for Tracked<F> {
+    fn drop(&mut self) {
+        if !self.completed {
+            tracing::debug!(parent: &self.header.span, "task dropped before completion");
+        }
+        TASKS.lock().unwrap().remove(&self.header.id);
+    }
+}
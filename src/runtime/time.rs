@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+/// Deadlines are served by one background thread, so timers work on any
+/// executor, not just this runtime's workers.
+struct Timer {
+    state: Mutex<TimerState>,
+    changed: Condvar,
+}
+
+struct TimerState {
+    next_id: u64,
+    timers: BTreeMap<(Instant, u64), Waker>,
+}
+
+static TIMER: Lazy<&'static Timer> = Lazy::new(|| {
+    let timer: &'static Timer = Box::leak(Box::new(Timer {
+        state: Mutex::new(TimerState {
+            next_id: 0,
+            timers: BTreeMap::new(),
+        }),
+        changed: Condvar::new(),
+    }));
+    thread::Builder::new()
+        .name("runtime-timer".into())
+        .spawn(move || timer.run())
+        .expect("failed to spawn timer thread");
+    timer
+});
+
+impl Timer {
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let later = state.timers.split_off(&(now, u64::MAX));
+            let due = std::mem::replace(&mut state.timers, later);
+            if !due.is_empty() {
+                drop(state);
+                due.into_values().for_each(Waker::wake);
+                state = self.state.lock().unwrap();
+                continue;
+            }
+            state = match state.timers.first_key_value() {
+                Some((&(deadline, _), _)) => {
+                    self.changed
+                        .wait_timeout(state, deadline.saturating_duration_since(now))
+                        .unwrap()
+                        .0
+                }
+                None => self.changed.wait(state).unwrap(),
+            };
+        }
+    }
+}
+
+/// Waits until `duration` has elapsed.
+///
+/// Inside a [`crate::test`] runtime this uses the virtual clock instead.
+pub fn sleep(duration: Duration) -> Sleep {
+    if crate::test::is_active() {
+        return Sleep {
+            inner: SleepInner::Virtual(crate::test::sleep(duration)),
+        };
+    }
+    Sleep {
+        inner: SleepInner::Real {
+            deadline: Instant::now() + duration,
+            key: None,
+        },
+    }
+}
+
+pub struct Sleep {
+    inner: SleepInner,
+}
+
+enum SleepInner {
+    Real {
+        deadline: Instant,
+        key: Option<(Instant, u64)>,
+    },
+    Virtual(crate::test::Sleep),
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let (deadline, key) = match &mut self.inner {
+            SleepInner::Virtual(sleep) => return Pin::new(sleep).poll(cx),
+            SleepInner::Real { deadline, key } => (*deadline, key),
+        };
+        if Instant::now() >= deadline {
+            if let Some(key) = key.take() {
+                TIMER.state.lock().unwrap().timers.remove(&key);
+            }
+            return Poll::Ready(());
+        }
+
+        let mut state = TIMER.state.lock().unwrap();
+        let key = *key.get_or_insert_with(|| {
+            state.next_id += 1;
+            (deadline, state.next_id)
+        });
+        let earliest = state
+            .timers
+            .first_key_value()
+            .is_none_or(|(first, _)| key <= *first);
+        state.timers.insert(key, cx.waker().clone());
+        drop(state);
+        if earliest {
+            TIMER.changed.notify_one();
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let SleepInner::Real { key: Some(key), .. } = &self.inner {
+            TIMER.state.lock().unwrap().timers.remove(key);
+        }
+    }
+}
+
+/// Returned by [`timeout`] when the deadline passes first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Runs `future`, giving up if it has not finished after `duration`.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`; `Sleep` is `Unpin`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut this.sleep).poll(cx).map(|()| Err(Elapsed))
+    }
+}
@@ -0,0 +1,80 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tracing::Span;
+
+use super::task::current_span;
+
+/// `Instrument`-style helpers for futures passed to `spawn_task!`.
+///
+/// ```ignore
+/// spawn_task!(fetch(url).named("fetch").in_span(info_span!("crawl", %url)));
+/// ```
+pub trait TaskInstrument: Future + Sized {
+    /// Records `name` as the `task.name` field of the span of whichever
+    /// runtime task ends up polling this future.
+    fn named(self, name: impl Into<Cow<'static, str>>) -> Named<Self> {
+        Named {
+            future: self,
+            name: Some(name.into()),
+        }
+    }
+
+    /// Enters `span` on every poll, like `tracing::Instrument`, and marks it
+    /// as following from the span of the task that polls it, so the two can
+    /// be correlated even though `span` was created outside the task.
+    fn in_span(self, span: Span) -> InSpan<Self> {
+        InSpan {
+            future: self,
+            span,
+            linked: false,
+        }
+    }
+}
+
+impl<F: Future> TaskInstrument for F {}
+
+pub struct Named<F> {
+    future: F,
+    name: Option<Cow<'static, str>>,
+}
+
+impl<F: Future> Future for Named<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `future` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Some(name) = this.name.take() {
+            if let Some(task_span) = current_span() {
+                task_span.record("task.name", &*name);
+            }
+        }
+        unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx)
+    }
+}
+
+pub struct InSpan<F> {
+    future: F,
+    span: Span,
+    linked: bool,
+}
+
+impl<F: Future> Future for InSpan<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `future` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        if !this.linked {
+            this.linked = true;
+            if let Some(task_span) = current_span() {
+                this.span.follows_from(&task_span);
+            }
+        }
+        let _enter = this.span.enter();
+        unsafe { Pin::new_unchecked(&mut this.future) }.poll(cx)
+    }
+}
@@ -0,0 +1,284 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use futures_lite::future;
+use http::header::{self, HeaderValue};
+use http::{Request, Response, StatusCode, Version};
+
+use super::{
+    error_response, text_response, write_response, write_response_async, AsyncRequestReader,
+    Limits, ParseError, RequestReader, Router, Shutdown,
+};
+use crate::runtime;
+
+/// How often a blocking connection checks for shutdown while it waits.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+/// How long a connection is kept open for further requests.
+#[derive(Debug, Clone, Copy)]
+pub struct KeepAlive {
+    /// How long to wait for the client to send more. Must not be zero.
+    pub idle_timeout: Duration,
+    /// Requests served before the connection is closed; `1` turns
+    /// keep-alive off.
+    pub max_requests: usize,
+}
+
+impl Default for KeepAlive {
+    /// A 5 second idle timeout and 100 requests per connection.
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(5),
+            max_requests: 100,
+        }
+    }
+}
+
+/// Serves requests on `stream` with `router` until the client closes it,
+/// asks to, or `keep_alive` runs out.
+///
+/// Pipelined requests are answered in order. A client that takes longer
+/// than the idle timeout to send all of its next request is disconnected,
+/// with a `408` if it was partway through one.
+pub fn serve(
+    stream: TcpStream,
+    router: &Router,
+    limits: Limits,
+    keep_alive: KeepAlive,
+) -> io::Result<()> {
+    serve_until(stream, router, limits, keep_alive, &Shutdown::new())
+}
+
+/// [`serve`], but once `shutdown` is triggered the connection closes after
+/// the request in hand, or right away if it is idle.
+pub fn serve_until(
+    stream: TcpStream,
+    router: &Router,
+    limits: Limits,
+    keep_alive: KeepAlive,
+    shutdown: &Shutdown,
+) -> io::Result<()> {
+    // Responses are written whole; waiting to batch them only adds latency.
+    stream.set_nodelay(true)?;
+    let mut reader = RequestReader::new(
+        Deadline {
+            stream,
+            at: Instant::now(),
+        },
+        limits,
+    );
+    let mut served = 0;
+    loop {
+        let deadline = Instant::now() + keep_alive.idle_timeout;
+        reader.get_mut().at = deadline;
+        let next = loop {
+            match reader.next_request() {
+                Err(ParseError::Io(err)) if is_timeout(&err) => {
+                    if shutdown.is_triggered() && !reader.has_buffered() {
+                        break Ok(None);
+                    }
+                    if Instant::now() >= deadline {
+                        break Err(ParseError::Io(err));
+                    }
+                }
+                next => break next,
+            }
+        };
+        let request = match accept(next, reader.has_buffered()) {
+            Next::Request(request) => request,
+            Next::Reject(response) => return write_response(reader.get_mut(), &response),
+            Next::Close(result) => return result,
+        };
+        served += 1;
+        let close = last_request(&request, served, keep_alive);
+        let version = request.version();
+        let mut response = router.handle_blocking(request);
+        let close = set_connection(&mut response, version, close || shutdown.is_triggered());
+        write_response(reader.get_mut(), &response)?;
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// A blocking connection whose reads time out at `at`, however slowly the
+/// client trickles bytes in before then. Until that, reads also time out
+/// every [`SHUTDOWN_POLL`], as a blocked read cannot be interrupted to
+/// check for shutdown.
+struct Deadline {
+    stream: TcpStream,
+    at: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.at.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream
+            .set_read_timeout(Some(left.min(SHUTDOWN_POLL)))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Deadline {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// [`serve`] for connections driven by an async runtime.
+pub async fn serve_async(
+    io: impl AsyncRead + AsyncWrite + Unpin,
+    router: &Router,
+    limits: Limits,
+    keep_alive: KeepAlive,
+) -> io::Result<()> {
+    serve_async_until(io, router, limits, keep_alive, &Shutdown::new()).await
+}
+
+/// [`serve_until`] for connections driven by an async runtime.
+pub async fn serve_async_until(
+    io: impl AsyncRead + AsyncWrite + Unpin,
+    router: &Router,
+    limits: Limits,
+    keep_alive: KeepAlive,
+    shutdown: &Shutdown,
+) -> io::Result<()> {
+    let mut reader = AsyncRequestReader::new(io, limits);
+    let mut served = 0;
+    loop {
+        // Reading is cancel safe: bytes already read stay buffered.
+        let read = next_within(&mut reader, keep_alive.idle_timeout);
+        let next = future::or(async { Some(read.await) }, async {
+            shutdown.triggered().await;
+            None
+        })
+        .await;
+        let next = match next {
+            Some(next) => next,
+            None if !reader.has_buffered() => return Ok(()),
+            // Finish the request the client is partway through.
+            None => next_within(&mut reader, keep_alive.idle_timeout).await,
+        };
+        let request = match accept(next, reader.has_buffered()) {
+            Next::Request(request) => request,
+            Next::Reject(response) => {
+                return write_response_async(reader.get_mut(), &response).await;
+            }
+            Next::Close(result) => return result,
+        };
+        served += 1;
+        let close = last_request(&request, served, keep_alive);
+        let version = request.version();
+        let mut response = router.handle(request).await;
+        let close = set_connection(&mut response, version, close || shutdown.is_triggered());
+        write_response_async(reader.get_mut(), &response).await?;
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// The next request, or a timeout error if it takes longer than `idle`.
+async fn next_within<R: AsyncRead + Unpin>(
+    reader: &mut AsyncRequestReader<R>,
+    idle: Duration,
+) -> Result<Option<Request<Vec<u8>>>, ParseError> {
+    runtime::timeout(idle, reader.next_request())
+        .await
+        .unwrap_or_else(|_| Err(io::Error::from(io::ErrorKind::TimedOut).into()))
+}
+
+enum Next {
+    Request(Request<Vec<u8>>),
+    /// Answer with this, then close.
+    Reject(Response<Vec<u8>>),
+    Close(io::Result<()>),
+}
+
+fn accept(next: Result<Option<Request<Vec<u8>>>, ParseError>, partial: bool) -> Next {
+    let err = match next {
+        Ok(Some(request)) => return Next::Request(request),
+        Ok(None) => return Next::Close(Ok(())),
+        Err(err) => err,
+    };
+    if let Some(response) = error_response(&err) {
+        return Next::Reject(response);
+    }
+    match err {
+        ParseError::Io(err) if is_timeout(&err) => {
+            if !partial {
+                return Next::Close(Ok(()));
+            }
+            let mut response = text_response(StatusCode::REQUEST_TIMEOUT, "request timeout\n");
+            response
+                .headers_mut()
+                .insert(header::CONNECTION, HeaderValue::from_static("close"));
+            Next::Reject(response)
+        }
+        ParseError::Io(err) => Next::Close(Err(err)),
+        _ => unreachable!("only I/O errors have no response"),
+    }
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    // Blocking sockets report an expired read timeout as `WouldBlock` on
+    // Unix and `TimedOut` on Windows.
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Whether the connection closes after answering the `served`th request.
+fn last_request<B>(request: &Request<B>, served: usize, keep_alive: KeepAlive) -> bool {
+    !wants_keep_alive(request) || served >= keep_alive.max_requests
+}
+
+/// Whether the client asked to keep the connection open after `request`:
+/// by default for HTTP/1.1, only with `Connection: keep-alive` for HTTP/1.0.
+pub fn wants_keep_alive<B>(request: &Request<B>) -> bool {
+    let has = |token: &str| {
+        request
+            .headers()
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|item| item.trim().eq_ignore_ascii_case(token))
+    };
+    if has("close") {
+        return false;
+    }
+    request.version() >= Version::HTTP_11 || has("keep-alive")
+}
+
+/// Tells the client whether the connection stays open, and returns whether
+/// it closes. A handler can close it by setting `Connection: close`.
+fn set_connection(response: &mut Response<Vec<u8>>, version: Version, close: bool) -> bool {
+    let close = close
+        || response
+            .headers()
+            .get(header::CONNECTION)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"close"));
+    let value = match (close, version) {
+        (true, _) => Some("close"),
+        // HTTP/1.0 clients assume the connection closes unless told.
+        (false, Version::HTTP_10) => Some("keep-alive"),
+        (false, _) => None,
+    };
+    if let Some(value) = value {
+        response
+            .headers_mut()
+            .insert(header::CONNECTION, HeaderValue::from_static(value));
+    }
+    close
+}
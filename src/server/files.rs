@@ -0,0 +1,309 @@
+use std::fs::{File, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::header::{self, HeaderMap, HeaderValue};
+use http::{Method, Request, Response, StatusCode};
+use mime_guess::mime;
+
+use super::router::percent_decode;
+use super::{text_response, BoxResponse, FileBody, Handler, RequestExt};
+
+/// Serves the files under a directory.
+///
+/// Mount it on a route ending in `*path`, such as `/static/*path`, to serve
+/// the captured rest of the path; anywhere else it serves the whole request
+/// path. Paths that leave the root, through `..` or a symlink, are not
+/// found.
+///
+/// Responses carry a `Content-Type` guessed from the extension, and an
+/// `ETag` and `Last-Modified` so clients can revalidate with a `304 Not
+/// Modified`. A single byte `Range` is answered with `206 Partial Content`.
+/// File contents are streamed as the response is written, never read into
+/// memory.
+#[derive(Clone)]
+pub struct StaticFiles {
+    inner: Arc<Config>,
+}
+
+#[derive(Clone)]
+struct Config {
+    root: PathBuf,
+    index: String,
+    not_found: Option<String>,
+}
+
+impl StaticFiles {
+    /// Serves the files under `root`, with `index.html` for directories.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            inner: Arc::new(Config {
+                root: root.into(),
+                index: "index.html".to_string(),
+                not_found: None,
+            }),
+        }
+    }
+
+    /// The file served for a request naming a directory.
+    pub fn index(mut self, name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner).index = name.into();
+        self
+    }
+
+    /// A file under the root to answer with when nothing else is found,
+    /// instead of a plain `404`.
+    pub fn not_found(mut self, page: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.inner).not_found = Some(page.into());
+        self
+    }
+}
+
+impl Handler for StaticFiles {
+    fn call(&self, req: Request<Vec<u8>>) -> BoxResponse {
+        let config = Arc::clone(&self.inner);
+        // File system calls block, so they run on smol's blocking thread
+        // pool rather than the caller's executor.
+        Box::pin(smol::unblock(move || config.respond(&req)))
+    }
+}
+
+impl Config {
+    fn respond(&self, req: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            let mut response =
+                text_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed\n");
+            response
+                .headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static("GET, HEAD"));
+            return response;
+        }
+        let path = match req.param("path") {
+            Some(path) => Some(path.to_string()),
+            None => percent_decode(req.uri().path()),
+        };
+        let found = path
+            .and_then(|path| self.resolve(&path))
+            .map(|path| open(&path).map(|(file, metadata)| (path, file, metadata)));
+        match found {
+            Some(Ok((path, file, metadata))) => serve(req, &path, file, &metadata),
+            Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => {
+                text_response(StatusCode::INTERNAL_SERVER_ERROR, "internal server error\n")
+            }
+            _ => self.not_found_response(req),
+        }
+    }
+
+    /// The file `path` names under the root, or `None` if it would leave
+    /// the root or does not exist.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let mut full = self.root.clone();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => return None,
+                // Separators and drive prefixes on other platforms.
+                _ if segment.contains(['\\', ':', '\0']) => return None,
+                _ => full.push(segment),
+            }
+        }
+        let full = full.canonicalize().ok()?;
+        if !full.starts_with(self.root.canonicalize().ok()?) {
+            return None;
+        }
+        if full.is_dir() {
+            return Some(full.join(&self.index));
+        }
+        Some(full)
+    }
+
+    fn not_found_response(&self, req: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+        let page = self
+            .not_found
+            .as_deref()
+            .and_then(|page| self.resolve(page))
+            .and_then(|path| {
+                open(&path)
+                    .ok()
+                    .map(|(file, metadata)| (path, file, metadata))
+            });
+        let Some((path, file, metadata)) = page else {
+            return text_response(StatusCode::NOT_FOUND, "not found\n");
+        };
+        let len = metadata.len();
+        let mut response = Response::new(Vec::new());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, content_type(&path));
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        if req.method() != Method::HEAD {
+            response
+                .extensions_mut()
+                .insert(FileBody::new(file, 0, len));
+        }
+        response
+    }
+}
+
+fn open(path: &Path) -> io::Result<(File, Metadata)> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::ErrorKind::NotFound.into());
+    }
+    Ok((file, metadata))
+}
+
+fn serve(
+    req: &Request<Vec<u8>>,
+    path: &Path,
+    file: File,
+    metadata: &Metadata,
+) -> Response<Vec<u8>> {
+    let len = metadata.len();
+    // Whole seconds, as Last-Modified cannot carry more.
+    let modified_secs = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    let modified = modified_secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    let etag = format!("\"{:x}-{len:x}\"", modified_secs.unwrap_or(0));
+    let last_modified = modified.map(httpdate::fmt_http_date);
+
+    let mut response = Response::new(Vec::new());
+    let headers = response.headers_mut();
+    headers.insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    if let Some(date) = &last_modified {
+        headers.insert(header::LAST_MODIFIED, HeaderValue::from_str(date).unwrap());
+    }
+    if is_fresh(req.headers(), &etag, modified) {
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, content_type(path));
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    let range = match req.headers().get(header::RANGE) {
+        Some(range) if if_range_matches(req.headers(), &etag, last_modified.as_deref()) => {
+            parse_range(range, len)
+        }
+        _ => Range::Full,
+    };
+    let (offset, body_len) = match range {
+        Range::Full => (0, len),
+        Range::Partial(start, end) => {
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            let value = format!("bytes {start}-{end}/{len}");
+            response.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&value).unwrap(),
+            );
+            (start, end - start + 1)
+        }
+        Range::Unsatisfiable => {
+            *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            let value = format!("bytes */{len}");
+            response.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&value).unwrap(),
+            );
+            return response;
+        }
+    };
+    response
+        .headers_mut()
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+    if req.method() != Method::HEAD {
+        response
+            .extensions_mut()
+            .insert(FileBody::new(file, offset, body_len));
+    }
+    response
+}
+
+fn content_type(path: &Path) -> HeaderValue {
+    let guess = mime_guess::from_path(path).first_or_octet_stream();
+    let value = if guess.type_() == mime::TEXT && guess.get_param(mime::CHARSET).is_none() {
+        format!("{guess}; charset=utf-8")
+    } else {
+        guess.to_string()
+    };
+    HeaderValue::from_str(&value).expect("MIME types are valid header values")
+}
+
+/// Whether the client's cached copy is current (RFC 9110, 13.1.2 and
+/// 13.1.3). `If-None-Match` takes precedence over `If-Modified-Since`.
+fn is_fresh(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+        let Ok(value) = value.to_str() else {
+            return false;
+        };
+        // Weak comparison: `W/"x"` matches `"x"`.
+        return value
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag);
+    }
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// Whether a `Range` should be honoured given `If-Range`: only if the
+/// client's copy is the current one, by strong ETag or exact date.
+fn if_range_matches(headers: &HeaderMap, etag: &str, last_modified: Option<&str>) -> bool {
+    match headers.get(header::IF_RANGE).map(HeaderValue::to_str) {
+        None => true,
+        Some(Ok(value)) => value == etag || Some(value) == last_modified,
+        Some(Err(_)) => false,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Range {
+    Full,
+    /// First and last byte, inclusive.
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=` range against a file of `len` bytes. Ranges
+/// this does not understand, including several ranges at once, get the
+/// whole file, as RFC 9110 section 14.2 allows.
+fn parse_range(value: &HeaderValue, len: u64) -> Range {
+    let Some(spec) = value.to_str().ok().and_then(|v| v.strip_prefix("bytes=")) else {
+        return Range::Full;
+    };
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return Range::Full;
+    };
+    if first.is_empty() {
+        // `bytes=-n`: the last n bytes.
+        return match last.parse::<u64>() {
+            Ok(0) => Range::Unsatisfiable,
+            Ok(_) if len == 0 => Range::Unsatisfiable,
+            Ok(n) => Range::Partial(len.saturating_sub(n), len - 1),
+            Err(_) => Range::Full,
+        };
+    }
+    let Ok(start) = first.parse::<u64>() else {
+        return Range::Full;
+    };
+    let end = match last {
+        "" => u64::MAX,
+        last => match last.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return Range::Full,
+        },
+    };
+    if start >= len {
+        return Range::Unsatisfiable;
+    }
+    Range::Partial(start, end.min(len - 1))
+}
@@ -0,0 +1,23 @@
+//! The pieces of a small HTTP/1.1 server: reading requests off a
+//! connection, routing them to handlers and writing responses back, over
+//! persistent connections.
+//!
+//! Requests and responses are the `http` crate's types, with the body held
+//! in memory, or for responses, streamed from a file ([`FileBody`]).
+
+mod connection;
+mod files;
+mod request;
+mod response;
+mod router;
+mod shutdown;
+
+pub use connection::{
+    serve, serve_async, serve_async_until, serve_until, wants_keep_alive, KeepAlive,
+};
+pub use files::StaticFiles;
+pub use request::{AsyncRequestReader, Limits, ParseError, RequestReader};
+pub use response::{error_response, text_response, write_response, write_response_async, FileBody};
+pub(crate) use router::percent_decode;
+pub use router::{BoxResponse, Handler, Params, RequestExt, Router};
+pub use shutdown::{ConnectionGuard, Shutdown};
@@ -0,0 +1,485 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::mem;
+
+use futures::io::{AsyncRead, AsyncReadExt};
+use http::header::{self, HeaderName, HeaderValue};
+use http::{Method, Request, StatusCode, Uri, Version};
+
+/// Bounds on what a client may send, so one request cannot exhaust the
+/// server's memory.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Request line plus headers, in bytes.
+    pub max_head_bytes: usize,
+    pub max_headers: usize,
+    /// Decoded body, in bytes.
+    pub max_body_bytes: usize,
+}
+
+impl Default for Limits {
+    /// 8 KiB of head, 64 headers and a 1 MiB body.
+    fn default() -> Self {
+        Self {
+            max_head_bytes: 8 * 1024,
+            max_headers: 64,
+            max_body_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Why a request could not be read.
+#[derive(Debug)]
+pub enum ParseError {
+    /// Not valid HTTP/1.x.
+    Malformed(String),
+    /// The request line and headers exceed [`Limits::max_head_bytes`].
+    HeadTooLarge,
+    /// More than [`Limits::max_headers`] headers.
+    TooManyHeaders,
+    /// The body exceeds [`Limits::max_body_bytes`].
+    BodyTooLarge,
+    /// A transfer coding other than `chunked`.
+    UnsupportedEncoding(String),
+    /// The connection failed or closed partway through a request.
+    Io(io::Error),
+}
+
+impl ParseError {
+    /// The status to answer with, or `None` if the connection is unusable.
+    pub fn status(&self) -> Option<StatusCode> {
+        Some(match self {
+            ParseError::Malformed(_) => StatusCode::BAD_REQUEST,
+            ParseError::HeadTooLarge | ParseError::TooManyHeaders => {
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+            }
+            ParseError::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ParseError::UnsupportedEncoding(_) => StatusCode::NOT_IMPLEMENTED,
+            ParseError::Io(_) => return None,
+        })
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Malformed(reason) => write!(f, "malformed request: {reason}"),
+            ParseError::HeadTooLarge => f.write_str("request head too large"),
+            ParseError::TooManyHeaders => f.write_str("too many headers"),
+            ParseError::BodyTooLarge => f.write_str("request body too large"),
+            ParseError::UnsupportedEncoding(coding) => {
+                write!(f, "unsupported transfer coding: {coding}")
+            }
+            ParseError::Io(err) => write!(f, "connection error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+fn malformed(reason: impl Into<String>) -> ParseError {
+    ParseError::Malformed(reason.into())
+}
+
+/// Reads HTTP/1.x requests off a blocking connection, one after another.
+///
+/// Bytes read past the end of one request are kept for the next, so
+/// pipelined requests are not lost.
+pub struct RequestReader<R> {
+    io: R,
+    buf: Vec<u8>,
+    pending: Option<Pending>,
+    limits: Limits,
+}
+
+impl<R: Read> RequestReader<R> {
+    pub fn new(io: R, limits: Limits) -> Self {
+        Self {
+            io,
+            buf: Vec::new(),
+            pending: None,
+            limits,
+        }
+    }
+
+    /// The next request, or `None` if the client closed the connection
+    /// between requests.
+    pub fn next_request(&mut self) -> Result<Option<Request<Vec<u8>>>, ParseError> {
+        loop {
+            if let Some(request) = take_request(&mut self.buf, &mut self.pending, &self.limits)? {
+                return Ok(Some(request));
+            }
+            let mut chunk = [0; 8 * 1024];
+            let read = match self.io.read(&mut chunk) {
+                // A signal arrived; nothing was read.
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                read => read?,
+            };
+            if read == 0 {
+                return closed(&self.buf);
+            }
+            self.buf.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    /// Whether bytes of a further request are already buffered.
+    pub fn has_buffered(&self) -> bool {
+        !self.buf.is_empty()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.io
+    }
+
+    /// The connection, for writing responses.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.io
+    }
+}
+
+/// [`RequestReader`] for connections driven by an async runtime.
+pub struct AsyncRequestReader<R> {
+    io: R,
+    buf: Vec<u8>,
+    pending: Option<Pending>,
+    limits: Limits,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRequestReader<R> {
+    pub fn new(io: R, limits: Limits) -> Self {
+        Self {
+            io,
+            buf: Vec::new(),
+            pending: None,
+            limits,
+        }
+    }
+
+    /// The next request, or `None` if the client closed the connection
+    /// between requests.
+    pub async fn next_request(&mut self) -> Result<Option<Request<Vec<u8>>>, ParseError> {
+        loop {
+            if let Some(request) = take_request(&mut self.buf, &mut self.pending, &self.limits)? {
+                return Ok(Some(request));
+            }
+            let mut chunk = [0; 8 * 1024];
+            let read = self.io.read(&mut chunk).await?;
+            if read == 0 {
+                return closed(&self.buf);
+            }
+            self.buf.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    /// Whether bytes of a further request are already buffered.
+    pub fn has_buffered(&self) -> bool {
+        !self.buf.is_empty()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.io
+    }
+
+    /// The connection, for writing responses.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.io
+    }
+}
+
+/// A request whose head has been read but whose body is still arriving.
+/// It is kept between reads so the head is parsed, and a chunked body
+/// decoded, only once.
+struct Pending {
+    head: Request<()>,
+    head_len: usize,
+    body: BodyKind,
+    chunked: ChunkedBody,
+}
+
+/// Removes the request at the start of `buf` if all of it has arrived.
+fn take_request(
+    buf: &mut Vec<u8>,
+    pending: &mut Option<Pending>,
+    limits: &Limits,
+) -> Result<Option<Request<Vec<u8>>>, ParseError> {
+    let request = match pending {
+        Some(request) => request,
+        None => {
+            let Some((head, head_len)) = parse_head(buf, limits)? else {
+                return Ok(None);
+            };
+            let body = body_kind(&head, limits)?;
+            pending.insert(Pending {
+                head,
+                head_len,
+                body,
+                chunked: ChunkedBody::default(),
+            })
+        }
+    };
+    let head_len = request.head_len;
+    let (body, end) = match request.body {
+        BodyKind::Empty => (Vec::new(), head_len),
+        BodyKind::Length(len) if buf.len() - head_len >= len => {
+            (buf[head_len..head_len + len].to_vec(), head_len + len)
+        }
+        BodyKind::Length(_) => return Ok(None),
+        BodyKind::Chunked => match request.chunked.decode(&buf[head_len..], limits)? {
+            Some(used) => (mem::take(&mut request.chunked.body), head_len + used),
+            None => return Ok(None),
+        },
+    };
+    let head = pending.take().expect("a request is pending").head;
+    buf.drain(..end);
+    Ok(Some(head.map(|()| body)))
+}
+
+/// What to report when the client closes the connection with `buf` left
+/// over: nothing between requests, an error partway through one.
+fn closed(buf: &[u8]) -> Result<Option<Request<Vec<u8>>>, ParseError> {
+    if buf.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed partway through a request",
+    )
+    .into())
+}
+
+/// Parses the request line and headers at the start of `buf`. Returns the
+/// request without its body and the length of the head, or `None` if more
+/// bytes are needed.
+pub(crate) fn parse_head(
+    buf: &[u8],
+    limits: &Limits,
+) -> Result<Option<(Request<()>, usize)>, ParseError> {
+    // Empty lines before the request line are ignored (RFC 9112, 2.2).
+    let skipped = buf
+        .iter()
+        .take_while(|&&b| b == b'\r' || b == b'\n')
+        .count();
+    let mut headers = vec![httparse::EMPTY_HEADER; limits.max_headers];
+    let mut parsed = httparse::Request::new(&mut headers);
+    // The skipped lines count toward the limit, or a client could send
+    // them forever.
+    let len = match parsed.parse(&buf[skipped..]) {
+        Ok(httparse::Status::Complete(len)) => len,
+        Ok(httparse::Status::Partial) if buf.len() >= limits.max_head_bytes => {
+            return Err(ParseError::HeadTooLarge);
+        }
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(httparse::Error::TooManyHeaders) => return Err(ParseError::TooManyHeaders),
+        Err(err) => return Err(malformed(err.to_string())),
+    };
+    if skipped + len > limits.max_head_bytes {
+        return Err(ParseError::HeadTooLarge);
+    }
+
+    let method = Method::from_bytes(parsed.method.unwrap_or_default().as_bytes())
+        .map_err(|_| malformed("invalid method"))?;
+    let target = parsed.path.unwrap_or_default();
+    let uri: Uri = target
+        .parse()
+        .map_err(|_| malformed(format!("invalid request target: {target:?}")))?;
+    let version = match parsed.version {
+        Some(0) => Version::HTTP_10,
+        _ => Version::HTTP_11,
+    };
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .version(version)
+        .body(())
+        .expect("request parts are valid");
+    for header in parsed.headers.iter() {
+        let name = HeaderName::from_bytes(header.name.as_bytes())
+            .map_err(|_| malformed(format!("invalid header name: {:?}", header.name)))?;
+        let value = HeaderValue::from_bytes(header.value)
+            .map_err(|_| malformed(format!("invalid value for {name}")))?;
+        request.headers_mut().append(name, value);
+    }
+    if version == Version::HTTP_11 && !request.headers().contains_key(header::HOST) {
+        return Err(malformed("HTTP/1.1 request without Host"));
+    }
+    Ok(Some((request, skipped + len)))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum BodyKind {
+    Empty,
+    Length(usize),
+    Chunked,
+}
+
+/// How the body of `head` is framed (RFC 9112, section 6.3).
+pub(crate) fn body_kind(head: &Request<()>, limits: &Limits) -> Result<BodyKind, ParseError> {
+    let headers = head.headers();
+    if headers.contains_key(header::TRANSFER_ENCODING) {
+        if head.version() == Version::HTTP_10 {
+            return Err(malformed("Transfer-Encoding in an HTTP/1.0 request"));
+        }
+        // Both framings at once is how requests get smuggled past proxies.
+        if headers.contains_key(header::CONTENT_LENGTH) {
+            return Err(malformed("both Transfer-Encoding and Content-Length"));
+        }
+        let mut codings = Vec::new();
+        for value in headers.get_all(header::TRANSFER_ENCODING) {
+            let value = value
+                .to_str()
+                .map_err(|_| malformed("invalid Transfer-Encoding"))?;
+            codings.extend(
+                value
+                    .split(',')
+                    .map(|coding| coding.trim().to_ascii_lowercase())
+                    .filter(|coding| !coding.is_empty()),
+            );
+        }
+        return match &codings[..] {
+            [] => Err(malformed("empty Transfer-Encoding")),
+            [coding] if coding == "chunked" => Ok(BodyKind::Chunked),
+            [.., last] if last != "chunked" => {
+                Err(malformed("request body not terminated by chunked coding"))
+            }
+            _ => Err(ParseError::UnsupportedEncoding(codings.join(", "))),
+        };
+    }
+
+    let mut length = None;
+    for value in headers.get_all(header::CONTENT_LENGTH) {
+        for item in value
+            .to_str()
+            .map_err(|_| malformed("invalid Content-Length"))?
+            .split(',')
+        {
+            let item = item.trim();
+            if item.is_empty() || !item.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(malformed("invalid Content-Length"));
+            }
+            let parsed: u64 = item.parse().map_err(|_| ParseError::BodyTooLarge)?;
+            if length.is_some_and(|length| length != parsed) {
+                return Err(malformed("conflicting Content-Length values"));
+            }
+            length = Some(parsed);
+        }
+    }
+    match length {
+        None | Some(0) => Ok(BodyKind::Empty),
+        Some(length) if length > limits.max_body_bytes as u64 => Err(ParseError::BodyTooLarge),
+        Some(length) => Ok(BodyKind::Length(length as usize)),
+    }
+}
+
+/// Progress through a chunked body, decoded as it arrives. Chunk
+/// extensions and trailers are read and dropped.
+#[derive(Default)]
+pub(crate) struct ChunkedBody {
+    body: Vec<u8>,
+    /// Offset of the next chunk-size or trailer line.
+    pos: usize,
+    /// Whether the last chunk has been read and trailers follow.
+    trailers: bool,
+}
+
+impl ChunkedBody {
+    /// Decodes what has arrived of the chunked body at the start of `buf`,
+    /// which must hold everything passed before. Returns the number of
+    /// bytes the body took up once it is complete, or `None` if more are
+    /// needed.
+    pub(crate) fn decode(
+        &mut self,
+        buf: &[u8],
+        limits: &Limits,
+    ) -> Result<Option<usize>, ParseError> {
+        loop {
+            // Sizes, extensions and trailers may take up as much again as
+            // the body, so tiny chunks cannot make the buffer grow without
+            // bound.
+            if self.pos - self.body.len() > limits.max_body_bytes {
+                return Err(ParseError::BodyTooLarge);
+            }
+            let Some(line) = line_at(buf, self.pos, limits)? else {
+                return Ok(None);
+            };
+            if self.trailers {
+                // Trailer fields, up to an empty line.
+                self.pos = line.next;
+                if line.text.is_empty() {
+                    return Ok(Some(self.pos));
+                }
+                continue;
+            }
+
+            let size = line.text.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .ok()
+                .filter(|_| !size.is_empty() && size.bytes().all(|b| b.is_ascii_hexdigit()))
+                .ok_or_else(|| malformed("invalid chunk size"))?;
+            if size == 0 {
+                self.trailers = true;
+                self.pos = line.next;
+                continue;
+            }
+            // The size is the client's to choose, so it is checked before
+            // any arithmetic is done with it.
+            if size > limits.max_body_bytes.saturating_sub(self.body.len()) {
+                return Err(ParseError::BodyTooLarge);
+            }
+            let Some(end) = line.next.checked_add(size) else {
+                return Err(ParseError::BodyTooLarge);
+            };
+            let Some(next) = end.checked_add(2) else {
+                return Err(ParseError::BodyTooLarge);
+            };
+            if buf.len() < next {
+                // The size line is read again once more has arrived.
+                return Ok(None);
+            }
+            if &buf[end..next] != b"\r\n" {
+                return Err(malformed("chunk not followed by CRLF"));
+            }
+            self.body.extend_from_slice(&buf[line.next..end]);
+            self.pos = next;
+        }
+    }
+}
+
+struct Line<'a> {
+    text: &'a str,
+    /// Offset of the byte after the line ending.
+    next: usize,
+}
+
+/// The CRLF (or bare LF) terminated line starting at `start`.
+fn line_at<'a>(
+    buf: &'a [u8],
+    start: usize,
+    limits: &Limits,
+) -> Result<Option<Line<'a>>, ParseError> {
+    let rest = &buf[start..];
+    let Some(end) = rest.iter().position(|&b| b == b'\n') else {
+        if rest.len() > limits.max_head_bytes {
+            return Err(ParseError::HeadTooLarge);
+        }
+        return Ok(None);
+    };
+    let line = rest[..end].strip_suffix(b"\r").unwrap_or(&rest[..end]);
+    let text = std::str::from_utf8(line).map_err(|_| malformed("invalid chunk framing"))?;
+    Ok(Some(Line {
+        text,
+        next: start + end + 1,
+    }))
+}
@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use futures::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use http::header::{self, HeaderValue};
+use http::{Response, StatusCode};
+
+use super::ParseError;
+
+/// A response body read from a file as it is written, instead of held in
+/// memory. Put it in the response's extensions, with an empty body.
+#[derive(Debug)]
+pub struct FileBody {
+    file: File,
+    offset: u64,
+    len: u64,
+}
+
+impl FileBody {
+    /// `len` bytes of `file`, starting at `offset`.
+    pub fn new(file: File, offset: u64, len: u64) -> Self {
+        Self { file, offset, len }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Writes `response` as HTTP/1.1, followed by its [`FileBody`] if it has
+/// one. A `Content-Length` is added for the body unless the response
+/// already has one, as answers to `HEAD` do.
+pub fn write_response(out: &mut impl Write, response: &Response<Vec<u8>>) -> io::Result<()> {
+    out.write_all(&encode(response))?;
+    if let Some(body) = response.extensions().get::<FileBody>() {
+        let mut file = &body.file;
+        file.seek(SeekFrom::Start(body.offset))?;
+        let copied = io::copy(&mut file.take(body.len), out)?;
+        check_copied(copied, body)?;
+    }
+    out.flush()
+}
+
+/// [`write_response`] for connections driven by an async runtime.
+pub async fn write_response_async(
+    out: &mut (impl AsyncWrite + Unpin),
+    response: &Response<Vec<u8>>,
+) -> io::Result<()> {
+    out.write_all(&encode(response)).await?;
+    if let Some(body) = response.extensions().get::<FileBody>() {
+        let mut file = body.file.try_clone()?;
+        let offset = body.offset;
+        // Seeks and reads block, so they run on smol's blocking thread pool.
+        let file = smol::unblock(move || file.seek(SeekFrom::Start(offset)).map(|_| file)).await?;
+        let file = smol::Unblock::new(file).take(body.len);
+        let copied = futures::io::copy(file, out).await?;
+        check_copied(copied, body)?;
+    }
+    out.flush().await
+}
+
+/// The file shrank after the head promised its length, so the connection
+/// has to be dropped.
+fn check_copied(copied: u64, body: &FileBody) -> io::Result<()> {
+    if copied < body.len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "file ended before the response body",
+        ));
+    }
+    Ok(())
+}
+
+/// The head and in-memory body in one buffer, so they go out in one write
+/// rather than a small head segment that Nagle's algorithm would hold back.
+fn encode(response: &Response<Vec<u8>>) -> Vec<u8> {
+    let status = response.status();
+    let mut bytes = format!(
+        "HTTP/1.1 {} {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("Unknown")
+    )
+    .into_bytes();
+    for (name, value) in response.headers() {
+        bytes.extend_from_slice(name.as_str().as_bytes());
+        bytes.extend_from_slice(b": ");
+        bytes.extend_from_slice(value.as_bytes());
+        bytes.extend_from_slice(b"\r\n");
+    }
+    // 1xx, 204 and 304 responses never have a body to measure.
+    let bodiless = status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED;
+    if !bodiless && !response.headers().contains_key(header::CONTENT_LENGTH) {
+        let len = match response.extensions().get::<FileBody>() {
+            Some(body) => body.len,
+            None => response.body().len() as u64,
+        };
+        bytes.extend_from_slice(format!("content-length: {len}\r\n").as_bytes());
+    }
+    bytes.extend_from_slice(b"\r\n");
+    bytes.extend_from_slice(response.body());
+    bytes
+}
+
+/// A plain text response with `status` and `body`.
+pub fn text_response(status: StatusCode, body: impl Into<String>) -> Response<Vec<u8>> {
+    let mut response = Response::new(body.into().into_bytes());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+/// The response to send for `err`, if the connection can still take one.
+/// The connection should be closed after it.
+pub fn error_response(err: &ParseError) -> Option<Response<Vec<u8>>> {
+    let status = err.status()?;
+    let mut response = text_response(status, format!("{err}\n"));
+    response
+        .headers_mut()
+        .insert(header::CONNECTION, HeaderValue::from_static("close"));
+    Some(response)
+}
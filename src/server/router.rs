@@ -0,0 +1,308 @@
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use http::header::{self, HeaderValue};
+use http::{Method, Request, Response, StatusCode};
+
+use super::{text_response, FileBody};
+
+pub type BoxResponse = Pin<Box<dyn Future<Output = Response<Vec<u8>>> + Send>>;
+
+/// Something that answers requests. Implemented for async functions and
+/// closures taking a `Request<Vec<u8>>`.
+pub trait Handler: Send + Sync + 'static {
+    fn call(&self, req: Request<Vec<u8>>) -> BoxResponse;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(Request<Vec<u8>>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Response<Vec<u8>>> + Send + 'static,
+{
+    fn call(&self, req: Request<Vec<u8>>) -> BoxResponse {
+        Box::pin(self(req))
+    }
+}
+
+/// The path parameters a route captured, stored in the request's
+/// extensions. Read them with [`RequestExt::param`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+pub trait RequestExt {
+    /// The percent-decoded path parameter `name`, from `:name` or `*name`
+    /// in the matched route.
+    fn param(&self, name: &str) -> Option<&str>;
+}
+
+impl<B> RequestExt for Request<B> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.extensions().get::<Params>()?.get(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    /// `:name`, one path segment.
+    Param(String),
+    /// `*name`, the rest of the path; only allowed last.
+    Wildcard(String),
+}
+
+impl Segment {
+    /// Lower is more specific; used to pick between matching routes.
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Wildcard(_) => 2,
+        }
+    }
+}
+
+struct Route {
+    method: Method,
+    pattern: Vec<Segment>,
+    handler: Arc<dyn Handler>,
+}
+
+/// Dispatches requests to handlers by method and path pattern.
+///
+/// Patterns are matched segment by segment: `/users/:id` captures one
+/// segment as `id`, and `/files/*path` captures the rest of the path,
+/// slashes included, as `path`. When several routes match, static
+/// segments win over parameters and parameters over wildcards.
+///
+/// A path that matches no route gets a `404 Not Found`, or the fallback
+/// handler's answer. A path that matches only under other methods gets a
+/// `405 Method Not Allowed` with an `Allow` header. `HEAD` is answered by
+/// the `GET` handler, without the body.
+///
+/// Handlers are async, so a router serves connections on an async runtime
+/// directly and on a thread pool through [`handle_blocking`].
+///
+/// [`handle_blocking`]: Router::handle_blocking
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Option<Arc<dyn Handler>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` for `method` requests matching `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` does not start with `/`, has a wildcard before
+    /// its last segment, or has an unnamed parameter.
+    pub fn route(mut self, method: Method, pattern: &str, handler: impl Handler) -> Self {
+        self.routes.push(Route {
+            method,
+            pattern: parse_pattern(pattern),
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn get(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::GET, pattern, handler)
+    }
+
+    pub fn post(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::POST, pattern, handler)
+    }
+
+    pub fn put(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::PUT, pattern, handler)
+    }
+
+    pub fn delete(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::DELETE, pattern, handler)
+    }
+
+    /// Answers requests no route matches, instead of a plain `404`.
+    pub fn fallback(mut self, handler: impl Handler) -> Self {
+        self.fallback = Some(Arc::new(handler));
+        self
+    }
+
+    /// Runs the handler for `req` and returns its response.
+    pub fn handle(&self, req: Request<Vec<u8>>) -> BoxResponse {
+        let head = req.method() == Method::HEAD;
+        let path = req.uri().path().to_string();
+        let mut allowed = BTreeSet::new();
+        let mut best: Option<(&Route, Params)> = None;
+        for route in &self.routes {
+            let Some(params) = match_path(&route.pattern, &path) else {
+                continue;
+            };
+            allowed.insert(route.method.as_str());
+            let method_ok = route.method == req.method()
+                || (head && route.method == Method::GET && !self.has_head_route(&route.pattern));
+            let more_specific = best
+                .as_ref()
+                .is_none_or(|(best, _)| more_specific(&route.pattern, &best.pattern));
+            if method_ok && more_specific {
+                best = Some((route, params));
+            }
+        }
+
+        let Some((route, params)) = best else {
+            if allowed.is_empty() {
+                return match &self.fallback {
+                    Some(fallback) => fallback.call(req),
+                    None => Box::pin(async { text_response(StatusCode::NOT_FOUND, "not found\n") }),
+                };
+            }
+            if allowed.contains("GET") {
+                allowed.insert("HEAD");
+            }
+            let allow = allowed.into_iter().collect::<Vec<_>>().join(", ");
+            return Box::pin(async move {
+                let mut response =
+                    text_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed\n");
+                response.headers_mut().insert(
+                    header::ALLOW,
+                    HeaderValue::from_str(&allow).expect("method names are valid"),
+                );
+                response
+            });
+        };
+
+        let mut req = req;
+        req.extensions_mut().insert(params);
+        let response = route.handler.call(req);
+        if !head {
+            return response;
+        }
+        Box::pin(async move {
+            let mut response = response.await;
+            let length = match response.extensions_mut().remove::<FileBody>() {
+                Some(body) => body.len(),
+                None => response.body().len() as u64,
+            };
+            response
+                .headers_mut()
+                .entry(header::CONTENT_LENGTH)
+                .or_insert_with(|| HeaderValue::from(length));
+            response.body_mut().clear();
+            response
+        })
+    }
+
+    /// [`handle`](Self::handle) for threads outside an async runtime, such
+    /// as the workers of a [`ThreadPool`](crate::model::ThreadPool).
+    pub fn handle_blocking(&self, req: Request<Vec<u8>>) -> Response<Vec<u8>> {
+        futures_lite::future::block_on(self.handle(req))
+    }
+
+    fn has_head_route(&self, pattern: &[Segment]) -> bool {
+        self.routes
+            .iter()
+            .any(|route| route.method == Method::HEAD && route.pattern == pattern)
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let rest = pattern
+        .strip_prefix('/')
+        .unwrap_or_else(|| panic!("route {pattern:?} must start with '/'"));
+    let segments: Vec<_> = rest.split('/').collect();
+    let last = segments.len() - 1;
+    segments
+        .into_iter()
+        .enumerate()
+        .map(|(index, segment)| {
+            if let Some(name) = segment.strip_prefix(':') {
+                assert!(!name.is_empty(), "unnamed parameter in route {pattern:?}");
+                Segment::Param(name.to_string())
+            } else if let Some(name) = segment.strip_prefix('*') {
+                assert!(!name.is_empty(), "unnamed wildcard in route {pattern:?}");
+                assert!(index == last, "wildcard must end route {pattern:?}");
+                Segment::Wildcard(name.to_string())
+            } else {
+                Segment::Static(segment.to_string())
+            }
+        })
+        .collect()
+}
+
+fn match_path(pattern: &[Segment], path: &str) -> Option<Params> {
+    let mut rest = path.strip_prefix('/')?;
+    let mut params = Vec::new();
+    for (index, segment) in pattern.iter().enumerate() {
+        if let Segment::Wildcard(name) = segment {
+            params.push((name.clone(), percent_decode(rest)?));
+            return Some(Params(params));
+        }
+        let (part, remainder) = match rest.split_once('/') {
+            Some((part, remainder)) => (part, Some(remainder)),
+            None => (rest, None),
+        };
+        match segment {
+            Segment::Static(expected) if expected == part => {}
+            Segment::Param(name) if !part.is_empty() => {
+                params.push((name.clone(), percent_decode(part)?));
+            }
+            _ => return None,
+        }
+        match remainder {
+            Some(remainder) => rest = remainder,
+            // The path has ended; the pattern must end too.
+            None => return (index == pattern.len() - 1).then_some(Params(params)),
+        }
+    }
+    None
+}
+
+/// Whether `a` beats `b` on the first segment where their ranks differ.
+fn more_specific(a: &[Segment], b: &[Segment]) -> bool {
+    let ranks = |pattern: &[Segment]| pattern.iter().map(Segment::rank).collect::<Vec<_>>();
+    ranks(a) < ranks(b)
+}
+
+/// Decodes `%XX` escapes; `None` if they do not form valid UTF-8.
+pub(crate) fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| input.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use flume::{Receiver, Sender};
+
+/// Tells a server to stop, and waits for its connections to finish.
+///
+/// Clones share one state. Once [`trigger`](Self::trigger) is called,
+/// accept loops should stop and connections served with
+/// [`serve_until`](super::serve_until) close after the request in hand.
+/// Connections counted with [`track`](Self::track) can then be waited for
+/// with [`drain`](Self::drain).
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    triggered: AtomicBool,
+    /// Dropped on trigger, which disconnects `receiver` and wakes every
+    /// waiter.
+    sender: Mutex<Option<Sender<()>>>,
+    receiver: Receiver<()>,
+    active: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, receiver) = flume::bounded(0);
+        Self {
+            inner: Arc::new(Inner {
+                triggered: AtomicBool::new(false),
+                sender: Mutex::new(Some(sender)),
+                receiver,
+                active: Mutex::new(0),
+                finished: Condvar::new(),
+            }),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.inner.triggered.store(true, Ordering::SeqCst);
+        self.inner.sender.lock().unwrap().take();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.inner.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once [`trigger`](Self::trigger) has been called.
+    pub async fn triggered(&self) {
+        // Nothing is ever sent, so this only returns on disconnect.
+        let _ = self.inner.receiver.recv_async().await;
+    }
+
+    /// Counts a connection as in flight until the guard is dropped.
+    pub fn track(&self) -> ConnectionGuard {
+        *self.inner.active.lock().unwrap() += 1;
+        ConnectionGuard {
+            shutdown: self.clone(),
+        }
+    }
+
+    /// Connections currently tracked.
+    pub fn active(&self) -> usize {
+        *self.inner.active.lock().unwrap()
+    }
+
+    /// Blocks until no tracked connection is left, or `timeout` passes.
+    /// Returns whether they all finished.
+    pub fn drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut active = self.inner.active.lock().unwrap();
+        while *active > 0 {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return false;
+            }
+            active = self.inner.finished.wait_timeout(active, left).unwrap().0;
+        }
+        true
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An in-flight connection, from [`Shutdown::track`].
+pub struct ConnectionGuard {
+    shutdown: Shutdown,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let inner = &self.shutdown.inner;
+        let mut active = inner.active.lock().unwrap();
+        *active -= 1;
+        if *active == 0 {
+            inner.finished.notify_all();
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex as StdMutex, MutexGuard as StdMutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// Lets a fixed number of tasks wait for each other.
+///
+/// The barrier resets once all `n` tasks have arrived, so it can be reused.
+/// Exactly one task per generation is told it is the leader. A wait that is
+/// dropped before the barrier releases no longer counts as arrived.
+#[derive(Debug)]
+pub struct Barrier {
+    n: usize,
+    state: StdMutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    arrived: usize,
+    generation: u64,
+    next_id: u64,
+    wakers: HashMap<u64, Waker>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    /// A barrier of size zero behaves like a barrier of size one.
+    pub fn new(n: usize) -> Self {
+        Self {
+            n: n.max(1),
+            state: StdMutex::new(State {
+                arrived: 0,
+                generation: 0,
+                next_id: 0,
+                wakers: HashMap::new(),
+            }),
+        }
+    }
+
+    pub async fn wait(&self) -> BarrierWaitResult {
+        BarrierWait {
+            barrier: self,
+            slot: None,
+        }
+        .await
+    }
+
+    fn lock(&self) -> StdMutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+struct BarrierWait<'a> {
+    barrier: &'a Barrier,
+    /// Generation we arrived in and our key in `State::wakers`.
+    slot: Option<(u64, u64)>,
+}
+
+impl Future for BarrierWait<'_> {
+    type Output = BarrierWaitResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut state = this.barrier.lock();
+        match this.slot {
+            None => {
+                state.arrived += 1;
+                if state.arrived == this.barrier.n {
+                    state.arrived = 0;
+                    state.generation += 1;
+                    let wakers = std::mem::take(&mut state.wakers);
+                    drop(state);
+                    wakers.into_values().for_each(Waker::wake);
+                    return Poll::Ready(BarrierWaitResult(true));
+                }
+                let id = state.next_id;
+                state.next_id += 1;
+                state.wakers.insert(id, cx.waker().clone());
+                this.slot = Some((state.generation, id));
+                Poll::Pending
+            }
+            Some((generation, _)) if generation != state.generation => {
+                this.slot = None;
+                Poll::Ready(BarrierWaitResult(false))
+            }
+            Some((_, id)) => {
+                state.wakers.insert(id, cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for BarrierWait<'_> {
+    fn drop(&mut self) {
+        // Once the generation has moved on, our arrival was used up in
+        // releasing it.
+        if let Some((generation, id)) = self.slot {
+            let mut state = self.barrier.lock();
+            if generation == state.generation {
+                state.arrived -= 1;
+                state.wakers.remove(&id);
+            }
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Executor-agnostic async synchronization primitives.
+//!
+//! Waiters are parked with their [`Waker`](std::task::Waker) and released in
+//! FIFO order, so these types work on any executor, including the one in
+//! [`crate::runtime`].
+
+mod barrier;
+mod mutex;
+mod notify;
+mod rwlock;
+mod semaphore;
+
+pub use barrier::{Barrier, BarrierWaitResult};
+pub use mutex::{Mutex, MutexGuard, TryLockError};
+pub use notify::{Notified, Notify};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{
+    Acquire, AcquireError, OwnedSemaphorePermit, Semaphore, SemaphorePermit, TryAcquireError,
+};
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use crate::runtime::coop;
+
+/// Notifies one or all parked tasks.
+///
+/// [`notify_one`](Notify::notify_one) wakes the longest-waiting task, or
+/// stores a single permit if nobody is waiting, so a notification sent just
+/// before a task starts waiting is not lost. A task joins the wait queue the
+/// first time its [`Notified`] future is polled.
+pub struct Notify {
+    state: StdMutex<State>,
+}
+
+struct State {
+    permit: bool,
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+struct Waiter {
+    notified: AtomicBool,
+    waker: StdMutex<Option<Waker>>,
+}
+
+impl Notify {
+    pub fn new() -> Self {
+        Self {
+            state: StdMutex::new(State {
+                permit: false,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            waiter: None,
+            done: false,
+        }
+    }
+
+    pub fn notify_one(&self) {
+        let waker = {
+            let mut state = self.lock();
+            state.notify_one()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Wakes every task currently waiting. Does not store a permit.
+    pub fn notify_waiters(&self) {
+        let wakers = {
+            let mut state = self.lock();
+            state
+                .waiters
+                .drain(..)
+                .filter_map(|waiter| {
+                    waiter.notified.store(true, Ordering::Release);
+                    waiter.waker.lock().unwrap().take()
+                })
+                .collect::<Vec<_>>()
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    fn lock(&self) -> StdMutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Notify")
+            .field("permit", &state.permit)
+            .field("waiters", &state.waiters.len())
+            .finish()
+    }
+}
+
+impl State {
+    fn notify_one(&mut self) -> Option<Waker> {
+        match self.waiters.pop_front() {
+            Some(waiter) => {
+                waiter.notified.store(true, Ordering::Release);
+                waiter.waker.lock().unwrap().take()
+            }
+            None => {
+                self.permit = true;
+                None
+            }
+        }
+    }
+}
+
+/// Future returned by [`Notify::notified`].
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    waiter: Option<Arc<Waiter>>,
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        std::task::ready!(coop::poll_proceed(cx));
+        let this = &mut *self;
+        let mut state = this.notify.lock();
+        match &this.waiter {
+            None if state.permit => {
+                state.permit = false;
+                this.done = true;
+                Poll::Ready(())
+            }
+            None => {
+                let waiter = Arc::new(Waiter {
+                    notified: AtomicBool::new(false),
+                    waker: StdMutex::new(Some(cx.waker().clone())),
+                });
+                state.waiters.push_back(waiter.clone());
+                this.waiter = Some(waiter);
+                Poll::Pending
+            }
+            Some(waiter) if waiter.notified.load(Ordering::Acquire) => {
+                this.done = true;
+                Poll::Ready(())
+            }
+            Some(waiter) => {
+                let mut slot = waiter.waker.lock().unwrap();
+                match &*slot {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    _ => *slot = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else {
+            return;
+        };
+        if self.done {
+            return;
+        }
+        let waker = {
+            let mut state = self.notify.lock();
+            if waiter.notified.load(Ordering::Acquire) {
+                // We were picked by `notify_one` but went away before seeing
+                // it; pass the notification on instead of dropping it.
+                state.notify_one()
+            } else {
+                state.waiters.retain(|w| !Arc::ptr_eq(w, &waiter));
+                None
+            }
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
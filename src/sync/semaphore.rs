@@ -0,0 +1,312 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, MutexGuard as StdMutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use crate::runtime::coop;
+
+/// A fair counting semaphore.
+///
+/// Acquirers are queued in arrival order. A request for `n` permits at the
+/// head of the queue blocks everyone behind it until `n` permits are free,
+/// so large requests cannot be starved by a stream of small ones.
+pub struct Semaphore {
+    state: StdMutex<State>,
+}
+
+struct State {
+    permits: usize,
+    closed: bool,
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+struct Waiter {
+    needed: usize,
+    granted: AtomicBool,
+    waker: StdMutex<Option<Waker>>,
+}
+
+/// Returned by [`Semaphore::acquire`] when the semaphore has been closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcquireError;
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("semaphore closed")
+    }
+}
+
+impl std::error::Error for AcquireError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryAcquireError {
+    Closed,
+    NoPermits,
+}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryAcquireError::Closed => f.write_str("semaphore closed"),
+            TryAcquireError::NoPermits => f.write_str("no permits available"),
+        }
+    }
+}
+
+impl std::error::Error for TryAcquireError {}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            state: StdMutex::new(State {
+                permits,
+                closed: false,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.lock().permits
+    }
+
+    pub fn add_permits(&self, n: usize) {
+        let wakers = {
+            let mut state = self.lock();
+            state.permits += n;
+            state.dispatch()
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    pub fn acquire_many(&self, n: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            needed: n,
+            waiter: None,
+            done: false,
+        }
+    }
+
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    pub fn try_acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(TryAcquireError::Closed);
+        }
+        // Queued waiters go first, even if enough permits are free right now.
+        if !state.waiters.is_empty() || state.permits < n {
+            return Err(TryAcquireError::NoPermits);
+        }
+        state.permits -= n;
+        Ok(SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    pub async fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, AcquireError> {
+        self.acquire_many_owned(1).await
+    }
+
+    pub async fn acquire_many_owned(
+        self: Arc<Self>,
+        n: usize,
+    ) -> Result<OwnedSemaphorePermit, AcquireError> {
+        self.acquire_many(n).await?.forget();
+        Ok(OwnedSemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    /// Closes the semaphore, failing every pending and future acquire.
+    pub fn close(&self) {
+        let wakers = {
+            let mut state = self.lock();
+            state.closed = true;
+            state
+                .waiters
+                .drain(..)
+                .filter_map(|waiter| waiter.waker.lock().unwrap().take())
+                .collect::<Vec<_>>()
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    fn lock(&self) -> StdMutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Semaphore")
+            .field("permits", &state.permits)
+            .field("waiters", &state.waiters.len())
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl State {
+    /// Hands out permits to queued waiters, front to back, and returns the
+    /// wakers to call once the lock is released.
+    fn dispatch(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        while let Some(front) = self.waiters.front() {
+            if front.needed > self.permits {
+                break;
+            }
+            self.permits -= front.needed;
+            let waiter = self.waiters.pop_front().unwrap();
+            waiter.granted.store(true, Ordering::Release);
+            wakers.extend(waiter.waker.lock().unwrap().take());
+        }
+        wakers
+    }
+}
+
+/// Future returned by [`Semaphore::acquire`] and [`Semaphore::acquire_many`].
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    needed: usize,
+    waiter: Option<Arc<Waiter>>,
+    done: bool,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Result<SemaphorePermit<'a>, AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        std::task::ready!(coop::poll_proceed(cx));
+        let this = &mut *self;
+        let semaphore = this.semaphore;
+        let mut state = semaphore.lock();
+
+        let granted = match &this.waiter {
+            None if state.closed => false,
+            None if state.waiters.is_empty() && state.permits >= this.needed => {
+                state.permits -= this.needed;
+                true
+            }
+            None => {
+                let waiter = Arc::new(Waiter {
+                    needed: this.needed,
+                    granted: AtomicBool::new(false),
+                    waker: StdMutex::new(Some(cx.waker().clone())),
+                });
+                state.waiters.push_back(waiter.clone());
+                this.waiter = Some(waiter);
+                return Poll::Pending;
+            }
+            Some(waiter) if waiter.granted.load(Ordering::Acquire) => true,
+            Some(_) if state.closed => false,
+            Some(waiter) => {
+                let mut slot = waiter.waker.lock().unwrap();
+                match &*slot {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    _ => *slot = Some(cx.waker().clone()),
+                }
+                return Poll::Pending;
+            }
+        };
+
+        this.done = true;
+        if granted {
+            Poll::Ready(Ok(SemaphorePermit {
+                semaphore,
+                permits: this.needed,
+            }))
+        } else {
+            Poll::Ready(Err(AcquireError))
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(waiter) = self.waiter.take() else {
+            return;
+        };
+        if self.done {
+            return;
+        }
+        let wakers = {
+            let mut state = self.semaphore.lock();
+            if waiter.granted.load(Ordering::Acquire) {
+                // Granted but never observed: give the permits back.
+                state.permits += waiter.needed;
+            } else {
+                state.waiters.retain(|w| !Arc::ptr_eq(w, &waiter));
+            }
+            // Leaving the queue may unblock whoever was waiting behind us.
+            state.dispatch()
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// Permits borrowed from a [`Semaphore`], returned when dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Keeps the permits out of the semaphore instead of returning them.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+/// Permits held through an `Arc<Semaphore>`, returned when dropped.
+#[must_use]
+#[derive(Debug)]
+pub struct OwnedSemaphorePermit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+}
+
+impl OwnedSemaphorePermit {
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    pub fn semaphore(&self) -> &Arc<Semaphore> {
+        &self.semaphore
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! A deterministic, single-threaded runtime for tests.
+//!
+//! Every task spawned with [`spawn_task!`](crate::spawn_task) while a test
+//! runtime is active runs on the test thread. Ready tasks are picked in an
+//! order drawn from a seeded RNG, and [`sleep`] uses a virtual clock that
+//! jumps straight to the next deadline once every task is idle, so a test
+//! that sleeps for an hour finishes instantly.
+//!
+//! The seed is printed when a test panics. Set `RUST_CONCURRENCY_SEED` to
+//! that value, or pass it to [`Builder::seed`], to replay the same schedule.
+//!
+//! ```ignore
+//! #[rust_concurrency::test]
+//! async fn times_out() {
+//!     rust_concurrency::test::sleep(Duration::from_secs(3600)).await;
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use async_task::{Runnable, Task};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::runtime::coop;
+
+pub const SEED_ENV: &str = "RUST_CONCURRENCY_SEED";
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Shared>>> = const { RefCell::new(None) };
+}
+
+struct Shared {
+    seed: u64,
+    ready: Mutex<Vec<Runnable>>,
+    main_woken: AtomicBool,
+    clock: Mutex<Clock>,
+    /// Signalled when something is scheduled from outside the test thread.
+    unpark: Condvar,
+}
+
+struct Clock {
+    now: Duration,
+    next_timer: u64,
+    timers: BTreeMap<(Duration, u64), Waker>,
+}
+
+impl Clock {
+    /// Jumps to the earliest deadline and returns the wakers of every timer
+    /// due by then. Returns nothing if there are no timers.
+    fn advance(&mut self) -> Vec<Waker> {
+        let Some((&(deadline, _), _)) = self.timers.first_key_value() else {
+            return Vec::new();
+        };
+        self.now = self.now.max(deadline);
+        let later = self.timers.split_off(&(self.now, u64::MAX));
+        std::mem::replace(&mut self.timers, later)
+            .into_values()
+            .collect()
+    }
+}
+
+struct MainWaker(Arc<Shared>);
+
+impl Wake for MainWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.main_woken.store(true, Ordering::Release);
+        let _guard = self.0.ready.lock().unwrap();
+        self.0.unpark.notify_one();
+    }
+}
+
+/// Configures and runs a test runtime.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    seed: Option<u64>,
+    max_steps: u64,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            seed: None,
+            max_steps: 1_000_000,
+        }
+    }
+
+    /// Fixes the scheduling seed. Overrides `RUST_CONCURRENCY_SEED`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Panics if more than `max_steps` task polls happen, which usually
+    /// means a task is spinning.
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn block_on<F: Future>(self, future: F) -> F::Output {
+        let seed = self
+            .seed
+            .or_else(|| std::env::var(SEED_ENV).ok()?.parse().ok())
+            .unwrap_or_else(rand::random);
+        let shared = Arc::new(Shared {
+            seed,
+            ready: Mutex::new(Vec::new()),
+            main_woken: AtomicBool::new(true),
+            clock: Mutex::new(Clock {
+                now: Duration::ZERO,
+                next_timer: 0,
+                timers: BTreeMap::new(),
+            }),
+            unpark: Condvar::new(),
+        });
+        let _enter = Enter::new(shared.clone());
+        run(&shared, future, self.max_steps)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs `future` to completion on a fresh test runtime.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    Builder::new().block_on(future)
+}
+
+/// Installs the runtime for the current thread and reports the seed if the
+/// test panics.
+struct Enter(Arc<Shared>);
+
+impl Enter {
+    fn new(shared: Arc<Shared>) -> Self {
+        CURRENT.with(|current| {
+            let previous = current.borrow_mut().replace(shared.clone());
+            assert!(previous.is_none(), "test runtimes cannot be nested");
+        });
+        Self(shared)
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.borrow_mut().take());
+        if std::thread::panicking() {
+            eprintln!(
+                "test runtime seed: {seed} (rerun with {SEED_ENV}={seed})",
+                seed = self.0.seed
+            );
+        }
+        // Cancel whatever is left. Dropping a task may wake others, so
+        // release the lock first.
+        let leftover = std::mem::take(&mut *self.0.ready.lock().unwrap());
+        drop(leftover);
+    }
+}
+
+fn run<F: Future>(shared: &Arc<Shared>, future: F, max_steps: u64) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(MainWaker(shared.clone())));
+    let mut cx = Context::from_waker(&waker);
+    let mut rng = StdRng::seed_from_u64(shared.seed);
+    let mut steps = 0u64;
+
+    loop {
+        let next = {
+            let mut ready = shared.ready.lock().unwrap();
+            let main = shared.main_woken.load(Ordering::Acquire) as usize;
+            if ready.is_empty() && main == 0 {
+                None
+            } else {
+                let pick = rng.random_range(0..ready.len() + main);
+                if pick < ready.len() {
+                    Some(Some(ready.swap_remove(pick)))
+                } else {
+                    shared.main_woken.store(false, Ordering::Release);
+                    Some(None)
+                }
+            }
+        };
+
+        match next {
+            Some(Some(runnable)) => {
+                steps += 1;
+                assert!(
+                    steps <= max_steps,
+                    "test runtime exceeded {max_steps} steps"
+                );
+                coop::budgeted(|| runnable.run());
+            }
+            Some(None) => {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+            None => {
+                let fired = shared.clock.lock().unwrap().advance();
+                if fired.is_empty() {
+                    // Only something outside the runtime (another thread or
+                    // an I/O reactor) can make progress now.
+                    let ready = shared.ready.lock().unwrap();
+                    let _ready = shared
+                        .unpark
+                        .wait_while(ready, |ready| {
+                            ready.is_empty() && !shared.main_woken.load(Ordering::Acquire)
+                        })
+                        .unwrap();
+                } else {
+                    fired.into_iter().for_each(Waker::wake);
+                }
+            }
+        }
+    }
+}
+
+fn current() -> Option<Arc<Shared>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Whether the calling thread is running a test runtime.
+pub fn is_active() -> bool {
+    CURRENT.with(|current| current.borrow().is_some())
+}
+
+/// The seed of the active test runtime.
+pub fn seed() -> Option<u64> {
+    current().map(|shared| shared.seed)
+}
+
+/// Virtual time elapsed since the test runtime started.
+///
+/// # Panics
+///
+/// Panics if called outside a test runtime.
+pub fn now() -> Duration {
+    let shared = current().expect("`test::now` called outside a test runtime");
+    let now = shared.clock.lock().unwrap().now;
+    now
+}
+
+/// Spawns onto the active test runtime, if any, handing the future back
+/// otherwise.
+pub(crate) fn try_spawn<F>(future: F) -> Result<Task<F::Output>, F>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let Some(shared) = current() else {
+        return Err(future);
+    };
+    let weak = Arc::downgrade(&shared);
+    let schedule = move |runnable| {
+        if let Some(shared) = weak.upgrade() {
+            shared.ready.lock().unwrap().push(runnable);
+            shared.unpark.notify_one();
+        }
+    };
+    let (runnable, task) = async_task::spawn(future, schedule);
+    runnable.schedule();
+    Ok(task)
+}
+
+/// Waits until `duration` of virtual time has passed.
+///
+/// # Panics
+///
+/// The returned future panics if polled outside a test runtime.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        duration,
+        timer: None,
+    }
+}
+
+pub struct Sleep {
+    duration: Duration,
+    /// Deadline and key, once registered with the clock.
+    timer: Option<(Duration, u64)>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let shared = current().expect("`test::sleep` polled outside a test runtime");
+        let mut clock = shared.clock.lock().unwrap();
+        let key = match self.timer {
+            Some(key) => key,
+            None => {
+                let key = (clock.now + self.duration, clock.next_timer);
+                clock.next_timer += 1;
+                self.timer = Some(key);
+                key
+            }
+        };
+        if clock.now >= key.0 {
+            clock.timers.remove(&key);
+            return Poll::Ready(());
+        }
+        clock.timers.insert(key, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let (Some(key), Some(shared)) = (self.timer, current()) {
+            shared.clock.lock().unwrap().timers.remove(&key);
+        }
+    }
+}
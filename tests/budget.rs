@@ -0,0 +1,51 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use rust_concurrency::runtime::{spawn_task_function, yield_now, FutureType, Runtime};
+use rust_concurrency::spawn_task;
+
+/// Wakes itself on every poll until `stop` is set, like `BackgroundProcess`.
+struct Spin {
+    stop: Arc<AtomicBool>,
+}
+
+impl Future for Spin {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.stop.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[test]
+fn self_waking_task_cannot_starve_low_task() {
+    // A single high priority worker and no low priority workers: the only
+    // way the low task runs is if the worker leaves the high queue.
+    Runtime::new().with_high_num(1).with_low_num(0).run();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let spin = spawn_task!(Spin { stop: stop.clone() }, FutureType::High);
+
+    let (tx, rx) = flume::bounded(1);
+    spawn_task!(
+        async move {
+            yield_now().await;
+            stop.store(true, Ordering::Release);
+            tx.send(()).unwrap();
+        },
+        FutureType::Low
+    )
+    .detach();
+
+    rx.recv_timeout(Duration::from_secs(10))
+        .expect("low priority task was starved");
+    futures_lite::future::block_on(spin).unwrap();
+}
@@ -0,0 +1,93 @@
+use futures_lite::future;
+use http::Uri;
+use rust_concurrency::hyper_client::CookieJar;
+
+fn uri(uri: &str) -> Uri {
+    uri.parse().unwrap()
+}
+
+fn names(jar: &CookieJar, target: &str) -> Vec<String> {
+    jar.cookies(&uri(target))
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+#[test]
+fn cookies_match_domain_path_and_scheme() {
+    let jar = CookieJar::new();
+    let origin = uri("https://example.com/docs/page");
+    jar.store(&origin, "host=1");
+    jar.store(&origin, "wide=2; Domain=.Example.com; Path=/");
+    jar.store(&origin, "secure=3; Path=/; Secure");
+    jar.store(&origin, "foreign=4; Domain=other.com");
+
+    assert_eq!(
+        names(&jar, "https://example.com/docs/other"),
+        ["host", "wide", "secure"]
+    );
+    assert_eq!(names(&jar, "http://example.com/docs"), ["host", "wide"]);
+    assert_eq!(names(&jar, "http://example.com/docsearch"), ["wide"]);
+    assert_eq!(names(&jar, "http://www.example.com/docs/"), ["wide"]);
+    assert_eq!(names(&jar, "http://notexample.com/"), Vec::<String>::new());
+    assert_eq!(
+        jar.header(&uri("http://example.com/docs/x")).unwrap(),
+        "host=1; wide=2"
+    );
+}
+
+#[test]
+fn cookies_are_replaced_and_expire() {
+    let jar = CookieJar::new();
+    let origin = uri("http://example.com/");
+    jar.store(&origin, "a=1");
+    jar.store(&origin, "b=1");
+    jar.store(&origin, "a=2");
+    // Replacing keeps the original creation order.
+    assert_eq!(
+        jar.cookies(&origin),
+        [("a".into(), "2".into()), ("b".into(), "1".into())]
+    );
+
+    jar.store(&origin, "a=; Max-Age=0");
+    jar.store(&origin, "b=; Expires=Thu, 01 Jan 1970 00:00:00 GMT");
+    assert!(jar.cookies(&origin).is_empty());
+
+    // Max-Age wins over Expires, and the old dashed date format parses.
+    jar.store(
+        &origin,
+        "c=3; Max-Age=3600; Expires=Wed, 21-Oct-2015 07:28:00 GMT",
+    );
+    jar.store(&origin, "d=4; Expires=Wed, 21-Oct-2015 07:28:00 GMT");
+    assert_eq!(names(&jar, "http://example.com/"), ["c"]);
+}
+
+#[test]
+fn cookies_survive_a_save_and_load() {
+    let path = std::env::temp_dir().join(format!("cookies-{}.txt", std::process::id()));
+    let origin = uri("https://example.com/app/login");
+    let jar = CookieJar::new();
+    jar.store(&origin, "session=abc; HttpOnly");
+    jar.store(
+        &origin,
+        "pref=dark; Domain=example.com; Path=/; Max-Age=3600; Secure",
+    );
+    assert!(jar.is_changed());
+    future::block_on(jar.save(&path)).unwrap();
+    assert!(!jar.is_changed());
+
+    let loaded = CookieJar::new();
+    future::block_on(loaded.load(&path)).unwrap();
+    for target in [
+        "https://example.com/app/",
+        "https://www.example.com/app/",
+        "http://example.com/app/",
+    ] {
+        assert_eq!(
+            loaded.cookies(&uri(target)),
+            jar.cookies(&uri(target)),
+            "{target}"
+        );
+    }
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,380 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, Once};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_lite::future::block_on;
+use rust_concurrency::runtime::TaskInstrument;
+use rust_concurrency::runtime::{
+    current_task_id, spawn_task_function, FutureType, Runtime, TaskSnapshot, TaskState,
+};
+use rust_concurrency::spawn_task;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A span as [`Capture`] saw it.
+#[derive(Debug, Clone)]
+struct SpanInfo {
+    name: &'static str,
+    parent: Option<&'static str>,
+    fields: Fields,
+}
+
+/// An event recorded by [`Capture`], with the span it happened in.
+#[derive(Debug, Clone)]
+struct Captured {
+    level: Level,
+    message: String,
+    fields: Fields,
+    span: Option<SpanInfo>,
+}
+
+impl Captured {
+    fn in_task(&self, id: u64) -> bool {
+        self.span.as_ref().is_some_and(|span| {
+            span.name == "task" && span.fields.get("task.id") == Some(id.to_string())
+        })
+    }
+}
+
+static EVENTS: Mutex<Vec<Captured>> = Mutex::new(Vec::new());
+/// `(span, span it follows from)` pairs.
+static FOLLOWS: Mutex<Vec<(SpanInfo, SpanInfo)>> = Mutex::new(Vec::new());
+
+/// Records every span and event, from whichever thread emits it.
+struct Capture;
+
+impl<S> Layer<S> for Capture
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let info = SpanInfo {
+            name: span.name(),
+            parent: span.parent().map(|parent| parent.name()),
+            fields,
+        };
+        span.extensions_mut().insert(info);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: LayerContext<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let mut extensions = span.extensions_mut();
+        if let Some(info) = extensions.get_mut::<SpanInfo>() {
+            values.record(&mut info.fields);
+        }
+    }
+
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: LayerContext<'_, S>) {
+        let info = |id| {
+            ctx.span(id)
+                .and_then(|span| span.extensions().get::<SpanInfo>().cloned())
+        };
+        if let (Some(span), Some(follows)) = (info(id), info(follows)) {
+            FOLLOWS.lock().unwrap().push((span, follows));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let span = ctx
+            .event_span(event)
+            .and_then(|span| span.extensions().get::<SpanInfo>().cloned());
+        EVENTS.lock().unwrap().push(Captured {
+            level: *event.metadata().level(),
+            message: fields.get("message").unwrap_or_default(),
+            fields,
+            span,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Fields(Vec<(String, String)>);
+
+impl Fields {
+    fn get(&self, name: &str) -> Option<String> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_string(), value.to_string()));
+    }
+}
+
+/// Starts the runtime with one worker per priority, and serializes the
+/// tests, which share it.
+fn runtime() -> (Runtime, MutexGuard<'static, ()>) {
+    static SERIAL: Mutex<()> = Mutex::new(());
+    static START: Once = Once::new();
+    let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    START.call_once(|| {
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(Capture))
+            .unwrap();
+    });
+    let runtime = Runtime::new()
+        .with_high_num(1)
+        .with_low_num(1)
+        .with_long_poll_threshold(Duration::from_millis(50));
+    runtime.run();
+    (runtime, serial)
+}
+
+fn snapshot(runtime: &Runtime, id: u64) -> Option<TaskSnapshot> {
+    runtime
+        .task_dump()
+        .tasks
+        .into_iter()
+        .find(|task| task.id == id)
+}
+
+/// Blocks its worker until `gate` is released, after saying it started.
+async fn blocker(started: mpsc::Sender<()>, gate: Arc<Mutex<()>>) {
+    started.send(()).unwrap();
+    drop(gate.lock().unwrap());
+}
+
+#[test]
+fn metrics_count_spawns_completions_and_polls() {
+    let (runtime, _serial) = runtime();
+    let before = runtime.metrics();
+    let tasks: Vec<_> = (0..10)
+        .map(|i| spawn_task!(async move { i * 2 }, FutureType::High))
+        .collect();
+    for (i, task) in tasks.into_iter().enumerate() {
+        assert_eq!(block_on(task).unwrap(), i * 2);
+    }
+    let after = runtime.metrics();
+    assert!(after.spawned_tasks >= before.spawned_tasks + 10);
+    assert!(after.completed_tasks >= before.completed_tasks + 10);
+    assert!(after.total_polls >= before.total_polls + 10);
+    assert!(after.total_busy > before.total_busy);
+    assert!(after.mean_poll_duration() > Duration::ZERO);
+}
+
+#[test]
+fn task_dump_lists_waiting_tasks() {
+    let (runtime, _serial) = runtime();
+    let (id_tx, id_rx) = mpsc::channel();
+    let (done_tx, done_rx) = flume::bounded::<()>(1);
+    let line = line!() + 1;
+    let task = spawn_task!(async move {
+        id_tx.send(current_task_id().unwrap()).unwrap();
+        done_rx.recv_async().await.unwrap();
+    });
+    let id = id_rx.recv().unwrap();
+    // The task parks on the channel once its first poll returns.
+    let waiting = loop {
+        let task = snapshot(&runtime, id).expect("task is in the dump");
+        if task.state == TaskState::Idle {
+            break task;
+        }
+        std::thread::yield_now();
+    };
+    assert_eq!(waiting.location.file(), file!());
+    assert_eq!(waiting.location.line(), line);
+    assert!(matches!(waiting.priority, FutureType::Low));
+    assert_eq!(waiting.polls, 1);
+    let dump = runtime.task_dump().to_string();
+    assert!(dump.contains(&format!("#{id:<5} Low  idle")), "{dump}");
+
+    done_tx.send(()).unwrap();
+    block_on(task).unwrap();
+    assert!(
+        snapshot(&runtime, id).is_none(),
+        "finished tasks leave the dump"
+    );
+}
+
+/// Wakes itself on its first poll, after `gate` is released, then finishes
+/// on the next.
+struct WakesItself {
+    polled: Option<(mpsc::Sender<u64>, Arc<Mutex<()>>)>,
+}
+
+impl Future for WakesItself {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let Some((polled, gate)) = self.polled.take() else {
+            return Poll::Ready(());
+        };
+        polled.send(current_task_id().unwrap()).unwrap();
+        drop(gate.lock().unwrap());
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[test]
+fn tasks_that_wake_themselves_are_reported_scheduled() {
+    let (runtime, _serial) = runtime();
+    let (task_gate, blocker_gate) = (Arc::new(Mutex::new(())), Arc::new(Mutex::new(())));
+    let task_held = task_gate.lock().unwrap();
+    let blockers_held = blocker_gate.lock().unwrap();
+    let (polled_tx, polled_rx) = mpsc::channel();
+    let task = spawn_task!(WakesItself {
+        polled: Some((polled_tx, task_gate.clone())),
+    });
+    let id = polled_rx.recv().unwrap();
+
+    // Occupy the other worker, and queue a second blocker so the worker
+    // polling the task takes that next, leaving the task in the queue.
+    let (started_tx, started_rx) = mpsc::channel();
+    let first = spawn_task!(blocker(started_tx.clone(), blocker_gate.clone()));
+    started_rx.recv().unwrap();
+    let second = spawn_task!(blocker(started_tx, blocker_gate.clone()));
+    assert_eq!(snapshot(&runtime, id).unwrap().state, TaskState::Running);
+    drop(task_held);
+    started_rx.recv().unwrap();
+    let waiting = snapshot(&runtime, id).unwrap();
+    assert_eq!(waiting.state, TaskState::Scheduled);
+    assert_eq!(waiting.polls, 1);
+
+    drop(blockers_held);
+    for task in [first, second] {
+        block_on(task).unwrap();
+    }
+    block_on(task).unwrap();
+}
+
+#[test]
+fn long_polls_are_counted_and_logged() {
+    let (runtime, _serial) = runtime();
+    let before = runtime.metrics().long_polls;
+    let (id_tx, id_rx) = mpsc::channel();
+    let (done_tx, done_rx) = flume::bounded::<()>(1);
+    let task = spawn_task!(async move {
+        std::thread::sleep(Duration::from_millis(80));
+        id_tx.send(current_task_id().unwrap()).unwrap();
+        done_rx.recv_async().await.unwrap();
+    });
+    let id = id_rx.recv().unwrap();
+    let slow = loop {
+        let task = snapshot(&runtime, id).unwrap();
+        if task.polls == 1 {
+            break task;
+        }
+        std::thread::yield_now();
+    };
+    assert_eq!(slow.long_polls, 1);
+    assert!(slow.max_poll >= Duration::from_millis(80));
+    assert!(runtime.metrics().long_polls > before);
+    assert!(runtime
+        .task_dump()
+        .to_string()
+        .contains("[WARN: 1 long poll(s)]"));
+    done_tx.send(()).unwrap();
+    block_on(task).unwrap();
+
+    let warning = EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|event| event.fields.get("task.id") == Some(id.to_string()))
+        .cloned()
+        .expect("a warning was logged");
+    assert_eq!(warning.level, Level::WARN);
+    assert_eq!(
+        warning.message,
+        "task poll exceeded the long poll threshold"
+    );
+}
+
+#[test]
+fn tasks_are_traced_in_their_own_spans() {
+    let (runtime, _serial) = runtime();
+    let (wake_tx, wake_rx) = flume::bounded::<()>(1);
+    let (id_tx, id_rx) = mpsc::channel();
+    let request = tracing::info_span!("request", request.id = 7);
+    let task = request.in_scope(|| {
+        spawn_task!(async move {
+            id_tx.send(current_task_id().unwrap()).unwrap();
+            wake_rx.recv_async().await.unwrap();
+            tracing::info!("fetching");
+        }
+        .named("fetch")
+        .in_span(tracing::info_span!("crawl", url = "/a")))
+    });
+    let id = id_rx.recv().unwrap();
+    while snapshot(&runtime, id).is_some_and(|task| task.state != TaskState::Idle) {
+        std::thread::yield_now();
+    }
+    wake_tx.send(()).unwrap();
+    block_on(task).unwrap();
+
+    let events: Vec<_> = EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|event| event.in_task(id))
+        .cloned()
+        .collect();
+    let messages: Vec<_> = events.iter().map(|event| event.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "task spawned",
+            "task scheduled",
+            "task woken",
+            "task scheduled",
+            "task completed"
+        ]
+    );
+    let task_span = events.last().unwrap().span.clone().unwrap();
+    assert_eq!(
+        task_span.parent,
+        Some("request"),
+        "spawned inside `request`"
+    );
+    assert_eq!(task_span.fields.get("task.name").as_deref(), Some("fetch"));
+    assert_eq!(
+        task_span.fields.get("task.priority").as_deref(),
+        Some("Low")
+    );
+    assert!(task_span
+        .fields
+        .get("task.location")
+        .unwrap()
+        .starts_with(file!()));
+    assert_eq!(events[1].fields.get("queue").as_deref(), Some("low"));
+    assert_eq!(events[4].level, Level::DEBUG);
+    assert_eq!(events[4].fields.get("polls").as_deref(), Some("2"));
+
+    // Events from the future are in its own span, which follows from the
+    // task's.
+    let fetching = EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|event| event.message == "fetching")
+        .cloned()
+        .unwrap();
+    let crawl = fetching.span.unwrap();
+    assert_eq!(crawl.name, "crawl");
+    assert_eq!(crawl.fields.get("url").as_deref(), Some("/a"));
+    assert!(FOLLOWS.lock().unwrap().iter().any(|(span, follows)| {
+        span.name == "crawl"
+            && follows.name == "task"
+            && follows.fields.get("task.id") == Some(id.to_string())
+    }));
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use rust_concurrency::runtime::{sleep, spawn_task_function, spawn_with_timeout, FutureType};
+use rust_concurrency::spawn_task;
+
+#[rust_concurrency::test]
+async fn panic_resolves_join_handle() {
+    let task = spawn_task!(async {
+        panic!("boom");
+    });
+    let err = task.await.unwrap_err();
+    assert!(err.is_panic());
+    assert_eq!(err.to_string(), "task panicked: boom");
+}
+
+#[rust_concurrency::test]
+async fn cancelled_task_is_reported() {
+    let task = spawn_task!(std::future::pending::<()>());
+    assert!(task.cancel().await.is_none());
+}
+
+#[rust_concurrency::test]
+async fn abort_handle_cancels_task() {
+    let task = spawn_task!(std::future::pending::<()>());
+    let abort = task.abort_handle();
+    spawn_task!(async move { abort.abort() }).await.unwrap();
+    assert!(task.await.unwrap_err().is_cancelled());
+}
+
+#[rust_concurrency::test]
+async fn spawn_with_timeout_reports_timeout() {
+    let slow = spawn_with_timeout(
+        sleep(Duration::from_secs(60)),
+        FutureType::Low,
+        Duration::from_secs(5),
+    );
+    let fast = spawn_with_timeout(async { 7 }, FutureType::High, Duration::from_secs(5));
+    assert!(slow.await.unwrap_err().is_timeout());
+    assert_eq!(fast.await.unwrap(), 7);
+    assert_eq!(rust_concurrency::test::now(), Duration::from_secs(5));
+}
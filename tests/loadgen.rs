@@ -0,0 +1,121 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn loadgen_measures_a_spawned_server() {
+    let json = std::env::temp_dir().join(format!("loadgen-{}.json", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_loadgen"))
+        .args(["--spawn", "pool", "--connections", "4", "--duration", "0.5"])
+        .arg("--server-bin")
+        .arg(env!("CARGO_BIN_EXE_rust-concurrency"))
+        .arg("--json")
+        .arg(&json)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        table.starts_with("| Server | Requests | Req/s |"),
+        "{table}"
+    );
+    assert!(table.contains("\n| pool | "), "{table}");
+
+    let results: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    let pool = &results["results"][0];
+    assert_eq!(pool["server"], "pool");
+    assert!(pool["requests"].as_u64().unwrap() > 0);
+    assert_eq!(pool["errors"]["io"], 0);
+    assert_eq!(pool["errors"]["status"], 0);
+    let latency = &pool["latency_ms"];
+    assert!(latency["p50"].as_f64() <= latency["p99"].as_f64());
+    assert!(latency["p99"].as_f64() <= latency["max"].as_f64());
+}
+
+/// Runs `loadgen` with one connection against `addr` and returns its JSON
+/// results.
+fn load(addr: std::net::SocketAddr, extra: &[&str]) -> serde_json::Value {
+    let json = std::env::temp_dir().join(format!(
+        "loadgen-{}-{}.json",
+        std::process::id(),
+        addr.port()
+    ));
+    let output = Command::new(env!("CARGO_BIN_EXE_loadgen"))
+        .args(["--addr", &addr.to_string(), "--connections", "1"])
+        .args(extra)
+        .arg("--json")
+        .arg(&json)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let results: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    results["results"][0].clone()
+}
+
+/// Answers every request on every connection with `response`, and counts
+/// the connections.
+fn canned_server(response: &'static [u8]) -> (std::net::SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut stream = stream.unwrap();
+            thread::spawn(move || {
+                let mut buf = [0; 4096];
+                let mut request = Vec::new();
+                while let Ok(n @ 1..) = stream.read(&mut buf) {
+                    request.extend_from_slice(&buf[..n]);
+                    while let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        request.drain(..end + 4);
+                        if stream.write_all(response).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    (addr, accepted)
+}
+
+#[test]
+fn chunked_responses_are_read_whole() {
+    let (addr, accepted) = canned_server(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+          5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Trailer: yes\r\n\r\n",
+    );
+    let results = load(addr, &["--duration", "0.3"]);
+    let requests = results["requests"].as_u64().unwrap();
+    assert!(requests > 1, "{results}");
+    assert_eq!(results["bytes"], requests * 11);
+    assert_eq!(results["errors"]["io"], 0);
+    // Misreading the framing would have broken the kept-alive connection.
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn timeouts_are_counted_apart_from_io_errors() {
+    // Takes connections but never answers.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let streams: Vec<_> = listener.incoming().collect();
+        drop(streams);
+    });
+    let results = load(addr, &["--duration", "0.5", "--timeout", "0.2"]);
+    assert_eq!(results["requests"], 0);
+    assert!(
+        results["errors"]["timeout"].as_u64().unwrap() >= 1,
+        "{results}"
+    );
+    assert_eq!(results["errors"]["io"], 0);
+    assert_eq!(results["errors"]["connect"], 0);
+}
@@ -0,0 +1,85 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use futures_lite::future::block_on;
+use rust_concurrency::runtime::{spawn_task_function, yield_now, Flavor, FutureType, Runtime};
+use rust_concurrency::spawn_task;
+
+fn runtime() -> Runtime {
+    let runtime = Runtime::thread_per_core().with_cores(2);
+    assert_eq!(runtime.flavor(), Flavor::ThreadPerCore);
+    runtime.run();
+    runtime
+}
+
+fn worker() -> String {
+    thread::current().name().unwrap_or_default().to_string()
+}
+
+#[test]
+fn tasks_stay_on_their_home_core() {
+    let _runtime = runtime();
+    let tasks: Vec<_> = (0..8)
+        .map(|i| {
+            let order = if i % 2 == 0 {
+                FutureType::High
+            } else {
+                FutureType::Low
+            };
+            spawn_task!(
+                async {
+                    let mut seen = vec![worker()];
+                    for _ in 0..20 {
+                        yield_now().await;
+                        seen.push(worker());
+                    }
+                    // Tasks spawned by a worker live on its core too.
+                    let child = spawn_task!(async { worker() }).await.unwrap();
+                    seen.push(child);
+                    seen
+                },
+                order
+            )
+        })
+        .collect();
+    let mut homes = Vec::new();
+    for task in tasks {
+        let seen = block_on(task).unwrap();
+        assert!(seen[0].starts_with("runtime-core-"), "{seen:?}");
+        assert!(seen.iter().all(|name| *name == seen[0]), "{seen:?}");
+        homes.push(seen[0].clone());
+    }
+    // Spawns from outside the runtime are spread round-robin.
+    homes.sort();
+    homes.dedup();
+    assert_eq!(homes, ["runtime-core-0", "runtime-core-1"]);
+}
+
+#[test]
+fn overloaded_cores_spill_to_their_peers() {
+    let _runtime = runtime();
+    let ran_on = Arc::new(Mutex::new(Vec::new()));
+    let parent = {
+        let ran_on = ran_on.clone();
+        spawn_task!(async move {
+            // This worker is busy until the loop ends, so everything spawned
+            // here piles up in its queue until it overflows.
+            let children: Vec<_> = (0..200)
+                .map(|_| {
+                    let ran_on = ran_on.clone();
+                    spawn_task!(async move { ran_on.lock().unwrap().push(worker()) })
+                })
+                .collect();
+            (worker(), children)
+        })
+    };
+    let (home, children) = block_on(parent).unwrap();
+    for child in children {
+        block_on(child).unwrap();
+    }
+    let ran_on = ran_on.lock().unwrap();
+    let at_home = ran_on.iter().filter(|name| **name == home).count();
+    assert_eq!(ran_on.len(), 200);
+    assert!(at_home >= 64, "home keeps its queue full: {at_home}");
+    assert!(at_home < 200, "the rest spill to the other core");
+}
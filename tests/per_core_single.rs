@@ -0,0 +1,27 @@
+use std::thread;
+
+use futures_lite::future::block_on;
+use rust_concurrency::runtime::{spawn_task_function, FutureType, Runtime};
+use rust_concurrency::spawn_task;
+
+#[test]
+fn one_core_runs_everything_without_spilling() {
+    // Zero is treated as one.
+    Runtime::thread_per_core().with_cores(0).run();
+    let parent = spawn_task!(async {
+        let children: Vec<_> = (0..200)
+            .map(|i| spawn_task!(async move { (i, thread::current().name().map(str::to_string)) }))
+            .collect();
+        let mut results = Vec::new();
+        for child in children {
+            results.push(child.await.unwrap());
+        }
+        results
+    });
+    let results = block_on(parent).unwrap();
+    assert_eq!(results.len(), 200);
+    for (i, (index, name)) in results.into_iter().enumerate() {
+        assert_eq!(index, i);
+        assert_eq!(name.as_deref(), Some("runtime-core-0"));
+    }
+}
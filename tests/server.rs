@@ -0,0 +1,644 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use http::{header, Method, Request, StatusCode, Version};
+use rust_concurrency::model::ThreadPool;
+use rust_concurrency::server::{
+    error_response, serve, serve_async, serve_async_until, serve_until, text_response,
+    write_response, AsyncRequestReader, KeepAlive, Limits, ParseError, RequestExt, RequestReader,
+    Router, Shutdown,
+};
+
+/// Hands out one byte per read, to exercise requests split across reads.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some((first, rest)) = self.0.split_first() else {
+            return Ok(0);
+        };
+        buf[0] = *first;
+        self.0 = rest;
+        Ok(1)
+    }
+}
+
+fn reader(input: &[u8]) -> RequestReader<&[u8]> {
+    RequestReader::new(input, Limits::default())
+}
+
+fn status_of(input: &[u8], limits: Limits) -> Option<StatusCode> {
+    RequestReader::new(input, limits)
+        .next_request()
+        .unwrap_err()
+        .status()
+}
+
+#[test]
+fn requests_are_parsed_in_full() {
+    let input = b"POST /items/7?sort=desc&q=a%20b HTTP/1.1\r\nHost: example.com\r\nX-Tag: one\r\nX-Tag: two\r\nContent-Length: 5\r\n\r\nhello";
+    let request = RequestReader::new(Trickle(input), Limits::default())
+        .next_request()
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.method(), Method::POST);
+    assert_eq!(request.uri().path(), "/items/7");
+    assert_eq!(request.uri().query(), Some("sort=desc&q=a%20b"));
+    assert_eq!(request.version(), Version::HTTP_11);
+    assert_eq!(request.headers()["host"], "example.com");
+    assert_eq!(request.headers().get_all("x-tag").iter().count(), 2);
+    assert_eq!(request.body(), b"hello");
+}
+
+#[test]
+fn http_1_0_requests_need_no_host() {
+    let request = reader(b"GET / HTTP/1.0\r\n\r\n")
+        .next_request()
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.version(), Version::HTTP_10);
+    assert_eq!(
+        status_of(b"GET / HTTP/1.1\r\n\r\n", Limits::default()),
+        Some(StatusCode::BAD_REQUEST)
+    );
+}
+
+#[test]
+fn chunked_bodies_are_decoded() {
+    let input = b"PUT /upload HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n5;name=ext\r\nhello\r\n7\r\n, world\r\n0\r\nX-Checksum: abc\r\n\r\n";
+    let request = RequestReader::new(Trickle(input), Limits::default())
+        .next_request()
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.body(), b"hello, world");
+}
+
+#[test]
+fn huge_chunk_sizes_are_refused() {
+    for size in ["fffffffffffffffe", "ffffffffffffffff", "100001"] {
+        let input = format!(
+            "POST / HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\n{size}\r\nabc\r\n"
+        );
+        assert_eq!(
+            status_of(input.as_bytes(), Limits::default()),
+            Some(StatusCode::PAYLOAD_TOO_LARGE),
+            "{size}"
+        );
+    }
+}
+
+#[test]
+fn endless_framing_is_refused() {
+    let limits = Limits {
+        max_body_bytes: 64,
+        ..Limits::default()
+    };
+    let blank_lines = "\r\n".repeat(10 * 1024);
+    assert_eq!(
+        status_of(blank_lines.as_bytes(), limits),
+        Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+    );
+    let tiny_chunks = format!(
+        "POST / HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n{}",
+        "1;pad=xxxxxxxxxxxxxxxx\r\na\r\n".repeat(32)
+    );
+    assert_eq!(
+        status_of(tiny_chunks.as_bytes(), limits),
+        Some(StatusCode::PAYLOAD_TOO_LARGE)
+    );
+    let trailers = format!(
+        "POST / HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n{}",
+        "X-Pad: 0123456789\r\n".repeat(32)
+    );
+    assert_eq!(
+        status_of(trailers.as_bytes(), limits),
+        Some(StatusCode::PAYLOAD_TOO_LARGE)
+    );
+}
+
+#[test]
+fn pipelined_requests_are_read_in_turn() {
+    let input =
+        b"\r\nGET /a HTTP/1.1\r\nHost: h\r\n\r\nPOST /b HTTP/1.1\r\nHost: h\r\nContent-Length: 2\r\n\r\nhiGET /c HTTP/1.0\r\n\r\n";
+    let mut reader = reader(input);
+    let mut paths = Vec::new();
+    while let Some(request) = reader.next_request().unwrap() {
+        paths.push(request.uri().path().to_string());
+    }
+    assert_eq!(paths, ["/a", "/b", "/c"]);
+}
+
+#[test]
+fn oversized_requests_are_refused() {
+    let limits = Limits {
+        max_head_bytes: 64,
+        max_headers: 2,
+        max_body_bytes: 4,
+    };
+    let long_path = format!("GET /{} HTTP/1.1\r\nHost: h\r\n\r\n", "a".repeat(100));
+    assert_eq!(
+        status_of(long_path.as_bytes(), limits),
+        Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+    );
+    assert_eq!(
+        status_of(b"GET / HTTP/1.1\r\nHost: h\r\nA: 1\r\nB: 2\r\n\r\n", limits),
+        Some(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+    );
+    assert_eq!(
+        status_of(
+            b"POST / HTTP/1.1\r\nHost: h\r\nContent-Length: 5\r\n\r\nhello",
+            limits
+        ),
+        Some(StatusCode::PAYLOAD_TOO_LARGE)
+    );
+    assert_eq!(
+        status_of(
+            b"POST / HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n",
+            limits
+        ),
+        Some(StatusCode::PAYLOAD_TOO_LARGE)
+    );
+}
+
+#[test]
+fn malformed_requests_are_rejected() {
+    let cases: [(&[u8], StatusCode); 6] = [
+        (b"GARBAGE\r\n\r\n", StatusCode::BAD_REQUEST),
+        (
+            b"GET / HTTP/1.1\r\nHost: h\r\nContent-Length: x\r\n\r\n",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            b"GET / HTTP/1.1\r\nHost: h\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nHost: h\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            b"POST / HTTP/1.1\r\nHost: h\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n",
+            StatusCode::NOT_IMPLEMENTED,
+        ),
+    ];
+    for (input, status) in cases {
+        assert_eq!(
+            status_of(input, Limits::default()),
+            Some(status),
+            "{}",
+            String::from_utf8_lossy(input)
+        );
+    }
+}
+
+#[test]
+fn closed_connections_end_the_stream() {
+    assert!(reader(b"").next_request().unwrap().is_none());
+    let err = reader(b"POST / HTTP/1.1\r\nHost: h\r\nContent-Length: 10\r\n\r\nshort")
+        .next_request()
+        .unwrap_err();
+    assert!(matches!(err, ParseError::Io(_)));
+    assert!(error_response(&err).is_none());
+}
+
+#[test]
+fn responses_are_written_with_a_length() {
+    let mut out = Vec::new();
+    write_response(&mut out, &text_response(StatusCode::NOT_FOUND, "gone")).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "HTTP/1.1 404 Not Found\r\ncontent-type: text/plain; charset=utf-8\r\ncontent-length: 4\r\n\r\ngone"
+    );
+}
+
+fn request(method: Method, path: &str) -> Request<Vec<u8>> {
+    Request::builder()
+        .method(method)
+        .uri(path)
+        .body(Vec::new())
+        .unwrap()
+}
+
+fn users() -> Router {
+    Router::new()
+        .get("/users/:id", |req: Request<Vec<u8>>| async move {
+            text_response(StatusCode::OK, format!("user {}", req.param("id").unwrap()))
+        })
+        .get("/users/me", |_| async {
+            text_response(StatusCode::OK, "me")
+        })
+        .delete("/users/:id", |_| async {
+            text_response(StatusCode::NO_CONTENT, "")
+        })
+        .get("/files/*path", |req: Request<Vec<u8>>| async move {
+            text_response(StatusCode::OK, req.param("path").unwrap().to_string())
+        })
+}
+
+#[test]
+fn routes_capture_params_and_prefer_static_segments() {
+    let router = users();
+    let body = |path| {
+        router
+            .handle_blocking(request(Method::GET, path))
+            .into_body()
+    };
+    assert_eq!(body("/users/42"), b"user 42");
+    assert_eq!(body("/users/a%20b"), b"user a b");
+    assert_eq!(body("/users/me"), b"me");
+    assert_eq!(body("/files/css/site.css"), b"css/site.css");
+    assert_eq!(body("/files/"), b"");
+}
+
+#[test]
+fn unrouted_requests_get_404_or_405() {
+    let router = users();
+    let missing = router.handle_blocking(request(Method::GET, "/users/42/posts"));
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    let wrong = router.handle_blocking(request(Method::POST, "/users/42"));
+    assert_eq!(wrong.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(wrong.headers()[header::ALLOW], "DELETE, GET, HEAD");
+
+    let router = users().fallback(|_| async { text_response(StatusCode::NOT_FOUND, "custom") });
+    let missing = router.handle_blocking(request(Method::GET, "/nope"));
+    assert_eq!(missing.into_body(), b"custom");
+}
+
+#[test]
+fn head_requests_use_the_get_handler_without_a_body() {
+    let response = users().handle_blocking(request(Method::HEAD, "/users/42"));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "7");
+    assert!(response.body().is_empty());
+    let mut out = Vec::new();
+    write_response(&mut out, &response).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .ends_with("content-length: 7\r\n\r\n"));
+}
+
+#[test]
+#[should_panic(expected = "wildcard must end route")]
+fn wildcards_must_come_last() {
+    let _ = Router::new().get("/files/*path/raw", |_| async {
+        text_response(StatusCode::OK, "")
+    });
+}
+
+#[rust_concurrency::test]
+async fn routers_serve_async_connections() {
+    let input: &[u8] = b"GET /users/7 HTTP/1.1\r\nHost: h\r\n\r\n";
+    let mut reader = AsyncRequestReader::new(input, Limits::default());
+    let request = reader.next_request().await.unwrap().unwrap();
+    let response = users().handle(request).await;
+    assert_eq!(response.into_body(), b"user 7");
+}
+
+/// Serves one connection on a background thread, sends `input` to it and
+/// returns everything it answered before closing.
+fn exchange(keep_alive: KeepAlive, input: &[u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        serve(stream, &users(), Limits::default(), keep_alive).unwrap();
+    });
+    let mut client = TcpStream::connect(addr).unwrap();
+    client.write_all(input).unwrap();
+    let mut output = String::new();
+    client.read_to_string(&mut output).unwrap();
+    server.join().unwrap();
+    output
+}
+
+fn quick_keep_alive() -> KeepAlive {
+    KeepAlive {
+        idle_timeout: Duration::from_millis(200),
+        ..KeepAlive::default()
+    }
+}
+
+#[test]
+fn pipelined_requests_share_a_connection() {
+    let output = exchange(
+        quick_keep_alive(),
+        b"GET /users/1 HTTP/1.1\r\nHost: h\r\n\r\nGET /users/2 HTTP/1.1\r\nHost: h\r\n\r\nGET /users/3 HTTP/1.1\r\nHost: h\r\nConnection: close\r\n\r\nGET /users/4 HTTP/1.1\r\nHost: h\r\n\r\n",
+    );
+    assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 3);
+    assert!(output.contains("user 1") && output.contains("user 3"));
+    assert!(!output.contains("user 4"));
+    assert!(output.ends_with("connection: close\r\ncontent-length: 6\r\n\r\nuser 3"));
+}
+
+#[test]
+fn http_1_0_connections_close_unless_kept_alive() {
+    let output = exchange(
+        quick_keep_alive(),
+        b"GET /users/1 HTTP/1.0\r\n\r\nGET /users/2 HTTP/1.0\r\n\r\n",
+    );
+    assert!(output.contains("connection: close") && !output.contains("user 2"));
+
+    let output = exchange(
+        quick_keep_alive(),
+        b"GET /users/1 HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /users/2 HTTP/1.0\r\n\r\n",
+    );
+    assert!(output.contains("connection: keep-alive\r\n"));
+    assert!(output.contains("user 2"));
+}
+
+#[test]
+fn connections_close_after_max_requests() {
+    let keep_alive = KeepAlive {
+        max_requests: 2,
+        ..quick_keep_alive()
+    };
+    let request = "GET /users/1 HTTP/1.1\r\nHost: h\r\n\r\n".repeat(3);
+    let output = exchange(keep_alive, request.as_bytes());
+    assert_eq!(output.matches("user 1").count(), 2);
+    assert_eq!(output.matches("connection: close").count(), 1);
+}
+
+#[test]
+fn idle_connections_time_out() {
+    let start = Instant::now();
+    let output = exchange(
+        quick_keep_alive(),
+        b"GET /users/1 HTTP/1.1\r\nHost: h\r\n\r\n",
+    );
+    assert!(output.contains("user 1") && !output.contains("connection: close"));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    let output = exchange(quick_keep_alive(), b"GET /users/1 HTTP/1.1\r\nHo");
+    assert!(output.starts_with("HTTP/1.1 408 Request Timeout"));
+}
+
+#[test]
+fn trickled_requests_time_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        serve(stream, &users(), Limits::default(), quick_keep_alive()).unwrap();
+    });
+    let client = TcpStream::connect(addr).unwrap();
+    let start = Instant::now();
+    let trickler = {
+        let mut client = client.try_clone().unwrap();
+        thread::spawn(move || {
+            let request = b"GET /users/1 HTTP/1.1\r\nX-Pad: ".iter();
+            for &byte in request.chain(std::iter::repeat(&b'a')) {
+                if client.write_all(&[byte]).is_err() || start.elapsed() > Duration::from_secs(5) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        })
+    };
+    let mut output = String::new();
+    (&client).read_to_string(&mut output).unwrap();
+    assert!(
+        output.starts_with("HTTP/1.1 408 Request Timeout"),
+        "{output}"
+    );
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        start.elapsed()
+    );
+    server.join().unwrap();
+    trickler.join().unwrap();
+}
+
+#[test]
+fn routed_connections_run_on_the_model_thread_pool() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = Arc::new(users().get("/panic", |_| async {
+        panic!("handler failed");
+    }));
+    let server = thread::spawn(move || {
+        // One worker, so the second connection needs it to survive the
+        // first.
+        let pool = ThreadPool::new(1);
+        for stream in listener.incoming().take(2) {
+            let (stream, router) = (stream.unwrap(), Arc::clone(&router));
+            pool.execute(move || {
+                serve(stream, &router, Limits::default(), quick_keep_alive()).unwrap();
+            });
+        }
+    });
+
+    let mut panicking = TcpStream::connect(addr).unwrap();
+    panicking
+        .write_all(b"GET /panic HTTP/1.1\r\nHost: h\r\n\r\n")
+        .unwrap();
+    let mut output = Vec::new();
+    panicking.read_to_end(&mut output).unwrap();
+    assert!(
+        output.is_empty(),
+        "the connection closes without a response"
+    );
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    client
+        .write_all(b"GET /users/3 HTTP/1.1\r\nHost: h\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut output = String::new();
+    client.read_to_string(&mut output).unwrap();
+    assert!(output.starts_with("HTTP/1.1 200 OK"), "{output}");
+    assert!(output.ends_with("user 3"));
+    server.join().unwrap();
+}
+
+#[test]
+fn async_connections_are_kept_alive() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let stream = smol::Async::new(stream).unwrap();
+        smol::block_on(serve_async(
+            stream,
+            &users(),
+            Limits::default(),
+            quick_keep_alive(),
+        ))
+        .unwrap();
+    });
+    let mut client = TcpStream::connect(addr).unwrap();
+    client
+        .write_all(
+            b"GET /users/1 HTTP/1.1\r\nHost: h\r\n\r\nDELETE /users/1 HTTP/1.1\r\nHost: h\r\n\r\n",
+        )
+        .unwrap();
+    let mut output = String::new();
+    client.read_to_string(&mut output).unwrap();
+    server.join().unwrap();
+    assert!(output.starts_with("HTTP/1.1 200 OK"));
+    assert!(output.contains("HTTP/1.1 204 No Content"));
+}
+
+#[test]
+fn shutdown_lets_requests_finish_and_closes_idle_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = Shutdown::new();
+    let router = Arc::new(users().get("/slow", |_| async {
+        std::thread::sleep(Duration::from_millis(300));
+        text_response(StatusCode::OK, "done")
+    }));
+    let server = {
+        let shutdown = shutdown.clone();
+        thread::spawn(move || {
+            let mut connections = Vec::new();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let (router, shutdown) = (Arc::clone(&router), shutdown.clone());
+                let guard = shutdown.track();
+                connections.push(thread::spawn(move || {
+                    serve_until(
+                        stream,
+                        &router,
+                        Limits::default(),
+                        KeepAlive::default(),
+                        &shutdown,
+                    )
+                    .unwrap();
+                    drop(guard);
+                }));
+            }
+            connections
+        })
+    };
+
+    let mut idle = TcpStream::connect(addr).unwrap();
+    idle.write_all(b"GET /users/1 HTTP/1.1\r\nHost: h\r\n\r\n")
+        .unwrap();
+    let mut response = [0; 1024];
+    let read = idle.read(&mut response).unwrap();
+    assert!(response[..read].ends_with(b"user 1"));
+
+    let mut busy = TcpStream::connect(addr).unwrap();
+    busy.write_all(b"GET /slow HTTP/1.1\r\nHost: h\r\n\r\n")
+        .unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let start = Instant::now();
+    shutdown.trigger();
+
+    let mut rest = Vec::new();
+    idle.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "idle connection closed promptly"
+    );
+    let mut output = String::new();
+    busy.read_to_string(&mut output).unwrap();
+    assert!(output.starts_with("HTTP/1.1 200 OK"));
+    assert!(output.contains("connection: close"));
+
+    assert!(shutdown.drain(Duration::from_secs(5)));
+    for connection in server.join().unwrap() {
+        connection.join().unwrap();
+    }
+}
+
+#[test]
+fn async_shutdown_lets_requests_finish_and_closes_idle_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = Shutdown::new();
+    let router = Arc::new(users().get("/slow", |_| async {
+        std::thread::sleep(Duration::from_millis(300));
+        text_response(StatusCode::OK, "done")
+    }));
+    let server = {
+        let shutdown = shutdown.clone();
+        thread::spawn(move || {
+            let mut connections = Vec::new();
+            for _ in 0..3 {
+                let (stream, _) = listener.accept().unwrap();
+                let (router, shutdown) = (Arc::clone(&router), shutdown.clone());
+                let guard = shutdown.track();
+                connections.push(thread::spawn(move || {
+                    let stream = smol::Async::new(stream).unwrap();
+                    smol::block_on(serve_async_until(
+                        stream,
+                        &router,
+                        Limits::default(),
+                        KeepAlive::default(),
+                        &shutdown,
+                    ))
+                    .unwrap();
+                    drop(guard);
+                }));
+            }
+            connections
+        })
+    };
+
+    let mut idle = TcpStream::connect(addr).unwrap();
+    idle.write_all(b"GET /users/1 HTTP/1.1\r\nHost: h\r\n\r\n")
+        .unwrap();
+    let mut response = [0; 1024];
+    let read = idle.read(&mut response).unwrap();
+    assert!(response[..read].ends_with(b"user 1"));
+
+    let mut busy = TcpStream::connect(addr).unwrap();
+    busy.write_all(b"GET /slow HTTP/1.1\r\nHost: h\r\n\r\n")
+        .unwrap();
+    let mut partial = TcpStream::connect(addr).unwrap();
+    partial.write_all(b"GET /users/2 HTTP/1.1\r\n").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let start = Instant::now();
+    shutdown.trigger();
+
+    let mut rest = Vec::new();
+    idle.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "idle connection closed promptly"
+    );
+    let mut output = String::new();
+    busy.read_to_string(&mut output).unwrap();
+    assert!(output.starts_with("HTTP/1.1 200 OK"));
+    assert!(output.contains("connection: close"));
+    // A request the client was partway through is still answered.
+    partial.write_all(b"Host: h\r\n\r\n").unwrap();
+    let mut output = String::new();
+    partial.read_to_string(&mut output).unwrap();
+    assert!(output.starts_with("HTTP/1.1 200 OK"), "{output}");
+    assert!(output.contains("connection: close"));
+    assert!(output.ends_with("user 2"));
+
+    assert!(shutdown.drain(Duration::from_secs(5)));
+    for connection in server.join().unwrap() {
+        connection.join().unwrap();
+    }
+}
+
+#[test]
+fn drain_waits_for_tracked_connections() {
+    let shutdown = Shutdown::new();
+    let guard = shutdown.track();
+    assert_eq!(shutdown.active(), 1);
+    assert!(!shutdown.drain(Duration::from_millis(50)));
+    let releaser = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        drop(guard);
+    });
+    assert!(shutdown.drain(Duration::from_secs(5)));
+    assert_eq!(shutdown.active(), 0);
+    releaser.join().unwrap();
+    assert!(!shutdown.is_triggered());
+    shutdown.trigger();
+    smol::block_on(shutdown.triggered());
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::PathBuf;
+
+use http::{header, Method, Request, Response, StatusCode};
+use rust_concurrency::server::{write_response, write_response_async, Router, StaticFiles};
+
+fn artifact(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 256) as u8).collect()
+}
+
+/// A fresh directory holding `site/` to serve and a `secret` beside it.
+fn site(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("static-files-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("site/docs")).unwrap();
+    fs::write(dir.join("site/index.html"), "<h1>home</h1>").unwrap();
+    fs::write(dir.join("site/docs/style.css"), "body {}").unwrap();
+    fs::write(dir.join("site/missing.html"), "<h1>lost</h1>").unwrap();
+    fs::write(dir.join("site/data.bin"), artifact(300 * 1024)).unwrap();
+    fs::write(dir.join("secret"), "password").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(dir.join("secret"), dir.join("site/link")).unwrap();
+    dir.join("site")
+}
+
+fn get(router: &Router, path: &str, headers: &[(&str, &str)]) -> (Response<Vec<u8>>, Vec<u8>) {
+    send(router, Method::GET, path, headers)
+}
+
+/// Routes a request and returns the response with its body as written.
+fn send(
+    router: &Router,
+    method: Method,
+    path: &str,
+    headers: &[(&str, &str)],
+) -> (Response<Vec<u8>>, Vec<u8>) {
+    let mut request = Request::builder().method(method).uri(path);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = router.handle_blocking(request.body(Vec::new()).unwrap());
+    let mut out = Vec::new();
+    write_response(&mut out, &response).unwrap();
+    let head_end = out.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    (response, out.split_off(head_end))
+}
+
+fn files(root: PathBuf) -> Router {
+    Router::new().get("/*path", StaticFiles::new(root).not_found("missing.html"))
+}
+
+#[test]
+fn files_are_served_with_their_type() {
+    let router = files(site("types"));
+    let (response, body) = get(&router, "/", &[]);
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/html; charset=utf-8"
+    );
+    assert_eq!(body, b"<h1>home</h1>");
+
+    let (response, body) = get(&router, "/docs/style.css", &[]);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/css; charset=utf-8"
+    );
+    assert_eq!(body, b"body {}");
+
+    let (response, body) = get(&router, "/data.bin", &[]);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/octet-stream"
+    );
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "307200");
+    assert!(response.body().is_empty(), "large files are streamed");
+    assert_eq!(body, artifact(300 * 1024));
+
+    let (response, body) = send(&router, Method::HEAD, "/data.bin", &[]);
+    assert_eq!(response.headers()[header::CONTENT_LENGTH], "307200");
+    assert!(body.is_empty());
+}
+
+#[test]
+fn paths_cannot_leave_the_root() {
+    let router = files(site("traversal"));
+    for path in [
+        "/../secret",
+        "/docs/../../secret",
+        "/%2e%2e/secret",
+        "/..%2fsecret",
+        "/link",
+    ] {
+        let (response, body) = get(&router, path, &[]);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+        assert_eq!(body, b"<h1>lost</h1>", "{path}");
+    }
+    let (response, _) = get(&router, "/docs/../index.html", &[]);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn unchanged_files_are_not_sent_again() {
+    let router = files(site("conditional"));
+    let (response, _) = get(&router, "/index.html", &[]);
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let modified = response.headers()[header::LAST_MODIFIED]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let weak = format!("W/{etag}");
+    for headers in [
+        [("if-none-match", etag.as_str())],
+        [("if-none-match", weak.as_str())],
+        [("if-modified-since", modified.as_str())],
+    ] {
+        let (response, body) = get(&router, "/index.html", &headers);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        assert!(body.is_empty());
+    }
+
+    let (response, _) = get(&router, "/index.html", &[("if-none-match", "\"other\"")]);
+    assert_eq!(response.status(), StatusCode::OK);
+    let (response, _) = get(
+        &router,
+        "/index.html",
+        &[("if-modified-since", "Thu, 01 Jan 1970 00:00:00 GMT")],
+    );
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn byte_ranges_are_served() {
+    let router = files(site("ranges"));
+    let content = artifact(300 * 1024);
+    let range = |value: &str| get(&router, "/data.bin", &[("range", value)]);
+
+    let (response, body) = range("bytes=10-19");
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers()[header::CONTENT_RANGE],
+        "bytes 10-19/307200"
+    );
+    assert_eq!(body, &content[10..20]);
+
+    let (_, body) = range("bytes=-5");
+    assert_eq!(body, &content[content.len() - 5..]);
+    let (_, body) = range("bytes=307000-");
+    assert_eq!(body, &content[307000..]);
+
+    let (response, body) = range("bytes=400000-");
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */307200");
+    assert!(body.is_empty());
+
+    for ignored in ["bytes=0-1,5-6", "lines=1-2", "bytes=9-3"] {
+        let (response, body) = range(ignored);
+        assert_eq!(response.status(), StatusCode::OK, "{ignored}");
+        assert_eq!(body.len(), content.len());
+    }
+
+    let (response, _) = get(
+        &router,
+        "/data.bin",
+        &[("range", "bytes=0-9"), ("if-range", "\"stale\"")],
+    );
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn files_stream_to_async_connections() {
+    let router = files(site("async"));
+    let request = Request::get("/data.bin")
+        .header("range", "bytes=100-")
+        .body(Vec::new())
+        .unwrap();
+    let out = smol::block_on(async {
+        let response = router.handle(request).await;
+        let mut out = Vec::new();
+        write_response_async(&mut out, &response).await.unwrap();
+        out
+    });
+    let content = artifact(300 * 1024);
+    assert!(out.starts_with(b"HTTP/1.1 206 Partial Content\r\n"));
+    assert!(out.ends_with(&content[100..]));
+}
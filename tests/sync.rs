@@ -0,0 +1,228 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use rust_concurrency::sync::{
+    AcquireError, Barrier, Mutex, Notify, RwLock, Semaphore, TryAcquireError,
+};
+
+/// Counts how often it is woken.
+#[derive(Default)]
+struct Wakes(AtomicUsize);
+
+impl Wake for Wakes {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Wakes {
+    fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A future polled by hand, outside any executor.
+struct Task<F: Future> {
+    future: Pin<Box<F>>,
+    wakes: Arc<Wakes>,
+}
+
+fn task<F: Future>(future: F) -> Task<F> {
+    Task {
+        future: Box::pin(future),
+        wakes: Arc::default(),
+    }
+}
+
+impl<F: Future> Task<F> {
+    fn poll(&mut self) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::clone(&self.wakes));
+        self.future.as_mut().poll(&mut Context::from_waker(&waker))
+    }
+
+    fn ready(&mut self) -> F::Output {
+        match self.poll() {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("still pending"),
+        }
+    }
+
+    fn woken(&self) -> bool {
+        self.wakes.count() > 0
+    }
+}
+
+#[test]
+fn semaphore_waiters_are_served_in_fifo_order() {
+    let semaphore = Semaphore::new(0);
+    let mut two = task(semaphore.acquire_many(2));
+    let mut first = task(semaphore.acquire());
+    let mut second = task(semaphore.acquire());
+    assert!(two.poll().is_pending() && first.poll().is_pending() && second.poll().is_pending());
+
+    // One permit is not enough for the head of the queue, and nobody may
+    // jump ahead of it.
+    semaphore.add_permits(1);
+    assert!(!two.woken() && !first.woken());
+    assert_eq!(
+        semaphore.try_acquire().unwrap_err(),
+        TryAcquireError::NoPermits
+    );
+
+    semaphore.add_permits(2);
+    assert!(two.woken() && first.woken() && !second.woken());
+    let permits = two.ready().unwrap();
+    let permit = first.ready().unwrap();
+    assert!(second.poll().is_pending());
+    drop(permits);
+    assert!(second.woken());
+    drop(second.ready().unwrap());
+    drop(permit);
+    assert_eq!(semaphore.available_permits(), 3);
+}
+
+#[test]
+fn cancelled_acquires_do_not_lose_permits() {
+    let semaphore = Semaphore::new(1);
+    let held = semaphore.try_acquire().unwrap();
+    let mut granted = task(semaphore.acquire());
+    assert!(granted.poll().is_pending());
+    drop(held);
+    assert!(granted.woken());
+    // Granted, but dropped before it saw the permit.
+    drop(granted);
+    assert_eq!(semaphore.available_permits(), 1);
+
+    // A cancelled waiter at the head of the queue lets the next one in.
+    let mut blocked = task(semaphore.acquire_many(2));
+    let mut behind = task(semaphore.acquire());
+    assert!(blocked.poll().is_pending() && behind.poll().is_pending());
+    drop(blocked);
+    assert!(behind.woken());
+    drop(behind.ready().unwrap());
+}
+
+#[test]
+fn closing_fails_pending_and_later_acquires() {
+    let semaphore = Semaphore::new(0);
+    let mut waiting = task(semaphore.acquire());
+    assert!(waiting.poll().is_pending());
+    semaphore.close();
+    assert!(waiting.woken());
+    assert_eq!(waiting.ready().unwrap_err(), AcquireError);
+    assert_eq!(
+        semaphore.try_acquire().unwrap_err(),
+        TryAcquireError::Closed
+    );
+    assert_eq!(task(semaphore.acquire()).ready().unwrap_err(), AcquireError);
+}
+
+#[test]
+fn mutex_is_handed_over_in_arrival_order() {
+    let mutex = Mutex::new(Vec::new());
+    let guard = mutex.try_lock().unwrap();
+    let mut first = task(async {
+        mutex.lock().await.push(1);
+    });
+    let mut second = task(async {
+        mutex.lock().await.push(2);
+    });
+    assert!(first.poll().is_pending() && second.poll().is_pending());
+    drop(guard);
+    assert!(first.woken() && !second.woken());
+    assert!(
+        mutex.try_lock().is_err(),
+        "the lock went to the first waiter"
+    );
+    first.ready();
+    assert!(second.woken());
+    second.ready();
+    assert_eq!(*mutex.try_lock().unwrap(), [1, 2]);
+}
+
+#[test]
+fn rwlock_writers_are_not_starved_by_readers() {
+    let lock = RwLock::new(0);
+    let reading = lock.try_read().unwrap();
+    let mut writer = task(async {
+        *lock.write().await += 1;
+    });
+    assert!(writer.poll().is_pending());
+
+    // Readers that arrive after a queued writer wait behind it.
+    assert!(lock.try_read().is_err());
+    let mut reader = task(async { *lock.read().await });
+    assert!(reader.poll().is_pending());
+
+    drop(reading);
+    assert!(writer.woken() && !reader.woken());
+    writer.ready();
+    assert_eq!(reader.ready(), 1);
+}
+
+#[test]
+fn notify_one_wakes_the_longest_waiting_task() {
+    let notify = Notify::new();
+    let mut first = task(notify.notified());
+    let mut second = task(notify.notified());
+    assert!(first.poll().is_pending() && second.poll().is_pending());
+    notify.notify_one();
+    assert!(first.woken() && !second.woken());
+    first.ready();
+    assert!(second.poll().is_pending());
+
+    // A notification picked up by a waiter that then goes away is passed
+    // on, not lost.
+    let mut third = task(notify.notified());
+    assert!(third.poll().is_pending());
+    notify.notify_one();
+    drop(second);
+    assert!(third.woken());
+    third.ready();
+}
+
+#[test]
+fn notify_one_is_stored_but_notify_waiters_is_not() {
+    let notify = Notify::new();
+    notify.notify_one();
+    notify.notify_one();
+    task(notify.notified()).ready();
+    assert!(
+        task(notify.notified()).poll().is_pending(),
+        "one permit at most"
+    );
+
+    let mut waiters: Vec<_> = (0..3).map(|_| task(notify.notified())).collect();
+    assert!(waiters.iter_mut().all(|waiter| waiter.poll().is_pending()));
+    notify.notify_waiters();
+    for waiter in &mut waiters {
+        waiter.ready();
+    }
+    assert!(task(notify.notified()).poll().is_pending());
+}
+
+#[test]
+fn barrier_releases_once_everyone_arrives() {
+    let barrier = Barrier::new(3);
+    let mut waiters: Vec<_> = (0..2).map(|_| task(barrier.wait())).collect();
+    assert!(waiters.iter_mut().all(|waiter| waiter.poll().is_pending()));
+
+    // A waiter that gives up no longer counts toward the barrier.
+    drop(waiters.pop());
+    let mut late = task(barrier.wait());
+    assert!(late.poll().is_pending());
+    assert!(!waiters[0].woken());
+
+    let leader = task(barrier.wait()).ready();
+    assert!(leader.is_leader());
+    assert!(waiters[0].woken() && late.woken());
+    assert!(!waiters[0].ready().is_leader());
+    assert!(!late.ready().is_leader());
+
+    // And it can be used again.
+    let mut next = task(barrier.wait());
+    assert!(next.poll().is_pending());
+}
@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rust_concurrency::runtime::{spawn_task_function, yield_now, FutureType};
+use rust_concurrency::spawn_task;
+use rust_concurrency::test::{self, Builder};
+
+#[rust_concurrency::test]
+async fn sleep_advances_virtual_clock() {
+    let short = spawn_task!(test::sleep(Duration::from_secs(60)));
+    test::sleep(Duration::from_secs(3600)).await;
+    short.await.unwrap();
+    assert_eq!(test::now(), Duration::from_secs(3600));
+}
+
+fn interleaving(seed: u64) -> Vec<usize> {
+    Builder::new().seed(seed).block_on(async {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let tasks: Vec<_> = (0..4)
+            .map(|id| {
+                let order = order.clone();
+                spawn_task!(async move {
+                    for _ in 0..3 {
+                        order.lock().unwrap().push(id);
+                        yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let order = order.lock().unwrap().clone();
+        order
+    })
+}
+
+#[test]
+fn same_seed_replays_same_schedule() {
+    assert_eq!(interleaving(7), interleaving(7));
+    let distinct = (0..16).map(interleaving).collect::<std::collections::HashSet<_>>();
+    assert!(distinct.len() > 1, "seeds should produce different schedules");
+}
@@ -0,0 +1,58 @@
+use std::process::{Command, Output};
+use std::thread;
+use std::time::Duration;
+
+use futures_lite::future::block_on;
+use rust_concurrency::runtime::{spawn_task_function, FutureType, Runtime, UnhandledPanic};
+use rust_concurrency::spawn_task;
+
+const SCENARIO: &str = "UNHANDLED_PANIC_SCENARIO";
+
+/// Runs [`scenario`] in a child process, since a runtime that aborts
+/// would take the test binary down with it.
+fn run(scenario: &str) -> Output {
+    Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "scenario", "--nocapture"])
+        .env(SCENARIO, scenario)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn scenario() {
+    // Only does something when run by `run`.
+    let Ok(scenario) = std::env::var(SCENARIO) else {
+        return;
+    };
+    Runtime::new()
+        .with_unhandled_panic(UnhandledPanic::ShutdownProcess)
+        .run();
+    match scenario.as_str() {
+        "awaited" => {
+            let err = block_on(spawn_task!(async { panic!("boom") })).unwrap_err();
+            assert!(err.is_panic());
+        }
+        "detached" => {
+            spawn_task!(async { panic!("boom") }).detach();
+            thread::sleep(Duration::from_secs(5));
+        }
+        scenario => panic!("unknown scenario {scenario:?}"),
+    }
+}
+
+#[test]
+fn panics_an_awaited_handle_reports_do_not_abort() {
+    let output = run("awaited");
+    assert!(output.status.success(), "{output:?}");
+}
+
+#[test]
+fn panics_in_detached_tasks_abort_the_process() {
+    let output = run("detached");
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("with no handle to report to; aborting"),
+        "{stderr}"
+    );
+}